@@ -0,0 +1,313 @@
+use std::{collections::HashMap, fmt};
+
+use anyhow::{anyhow, Result};
+use serde::{de::{IgnoredAny, MapAccess, SeqAccess, Visitor}, Deserialize, Deserializer};
+
+use crate::engine::{Clip, ClipFrame, Position, Rect, SheetSprite, Spritesheet, Trim};
+
+/// How long a frame stays up when the export does not say, Aseprite's own default.
+const DEFAULT_FRAME_DURATION: f64 = 0.1;
+
+#[derive(Deserialize)]
+struct AtlasRect {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+#[derive(Deserialize)]
+struct AtlasSize {
+    w: i32,
+    h: i32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Frame {
+    /// Upright size even when the frame is packed rotated.
+    frame: AtlasRect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    sprite_source_size: Option<AtlasRect>,
+    source_size: Option<AtlasSize>,
+    pivot: Option<Position>,
+    /// Milliseconds, only Aseprite writes it.
+    duration: Option<f64>,
+}
+
+impl Frame {
+    fn sprite(&self) -> SheetSprite {
+        let AtlasRect { x, y, w, h } = self.frame;
+        let trim = match (self.trimmed, &self.sprite_source_size, &self.source_size) {
+            (true, Some(offset), Some(size)) => Some(Trim { x: offset.x, y: offset.y, width: size.w, height: size.h }),
+            _ => None,
+        };
+
+        SheetSprite {
+            atlas: 0,
+            rect: if self.rotated { Rect { x, y, width: h, height: w } } else { Rect { x, y, width: w, height: h } },
+            rotated: self.rotated,
+            trim,
+            pivot: self.pivot,
+        }
+    }
+
+    fn duration(&self) -> f64 {
+        self.duration.map_or(DEFAULT_FRAME_DURATION, |duration| duration / 1000.0)
+    }
+}
+
+#[derive(Deserialize)]
+struct NamedFrame {
+    filename: String,
+    #[serde(flatten)]
+    frame: Frame,
+}
+
+/// Frames in the order the export lists them, which frame tags count in. Both tools
+/// write either a map keyed by name or an array of frames carrying their name.
+struct Frames(Vec<(String, Frame)>);
+
+impl<'de> Deserialize<'de> for Frames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = Frames;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map or an array of frames")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Frames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    frames.push(entry);
+                }
+                Ok(Frames(frames))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Frames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(NamedFrame { filename, frame }) = seq.next_element()? {
+                    frames.push((filename, frame));
+                }
+                Ok(Frames(frames))
+            }
+        }
+
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Direction {
+    #[default]
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse,
+}
+
+/// An Aseprite tag, playing the frames from `from` to `to` inclusive.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FrameTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: Direction,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Meta {
+    image: String,
+    #[serde(default)]
+    frame_tags: Vec<FrameTag>,
+}
+
+#[derive(Deserialize)]
+struct AtlasFile {
+    frames: Frames,
+    meta: Meta,
+    /// Frame names by clip, as TexturePacker writes them for PixiJS.
+    #[serde(default)]
+    animations: HashMap<String, Vec<String>>,
+}
+
+/// Only checks which layout a sheet uses. Reading the whole sheet into a
+/// `serde_json::Value` would sort the frames of a map by name, and frame tags count
+/// frames in the order the file lists them.
+#[derive(Deserialize)]
+struct Layout {
+    frames: Option<IgnoredAny>,
+}
+
+/// Reads the sheet at `path`, either in the plain layout `Spritesheet` deserializes or
+/// as a TexturePacker or Aseprite JSON export.
+pub fn read(json: &str, path: &str) -> Result<Spritesheet> {
+    let parse_error = |err: serde_json::Error| anyhow!("Could not parse {}: {}", path, err);
+
+    if serde_json::from_str::<Layout>(json).map_err(parse_error)?.frames.is_none() {
+        return serde_json::from_str(json).map_err(parse_error);
+    }
+
+    let file: AtlasFile = serde_json::from_str(json).map_err(parse_error)?;
+    import(file, path).map_err(|err| anyhow!("Could not import {}: {}", path, err))
+}
+
+/// Sprites keep their trim, rotation and pivot, frame tags and animations become clips
+/// that always loop. The image is found next to the JSON, as both tools write it.
+fn import(file: AtlasFile, path: &str) -> Result<Spritesheet> {
+    let frames = file.frames.0;
+
+    let mut tileset = HashMap::new();
+    for (name, frame) in frames.iter() {
+        if tileset.insert(name.clone(), frame.sprite()).is_some() {
+            return Err(anyhow!("{} is listed twice", name));
+        }
+    }
+
+    let mut clips = HashMap::new();
+    for tag in file.meta.frame_tags.iter() {
+        if tag.from > tag.to || tag.to >= frames.len() {
+            return Err(anyhow!("Frame tag {} runs from {} to {} but there are {} frames", tag.name, tag.from, tag.to, frames.len()));
+        }
+
+        let forward: Vec<usize> = (tag.from..=tag.to).collect();
+        let backward: Vec<usize> = forward.iter().rev().copied().collect();
+        let order = match tag.direction {
+            Direction::Forward => forward,
+            Direction::Reverse => backward,
+            Direction::Pingpong => bounce(forward),
+            Direction::PingpongReverse => bounce(backward),
+        };
+
+        let clip = Clip {
+            frames: order
+                .into_iter()
+                .map(|index| ClipFrame { sprite: frames[index].0.clone(), duration: frames[index].1.duration() })
+                .collect(),
+        };
+        if clips.insert(tag.name.clone(), clip).is_some() {
+            return Err(anyhow!("Frame tag {} is listed twice", tag.name));
+        }
+    }
+
+    for (name, names) in file.animations {
+        if names.is_empty() {
+            return Err(anyhow!("Animation {} has no frames", name));
+        }
+
+        let mut clip = Clip { frames: Vec::new() };
+        for frame_name in names {
+            let Some((_, frame)) = frames.iter().find(|(listed, _)| *listed == frame_name) else {
+                return Err(anyhow!("Animation {} shows {}, which is not a frame", name, frame_name));
+            };
+            clip.frames.push(ClipFrame { duration: frame.duration(), sprite: frame_name });
+        }
+        if clips.insert(name.clone(), clip).is_some() {
+            return Err(anyhow!("{} is both a frame tag and an animation", name));
+        }
+    }
+
+    Ok(Spritesheet { images: vec![beside(path, &file.meta.image)], tileset, clips })
+}
+
+/// There and back, without showing either end twice in a row when the clip loops.
+fn bounce(there: Vec<usize>) -> Vec<usize> {
+    let back: Vec<usize> = there.iter().rev().skip(1).take(there.len().saturating_sub(2)).copied().collect();
+    there.into_iter().chain(back).collect()
+}
+
+/// `image` as seen from the folder `path` is in, unless it is absolute already.
+fn beside(path: &str, image: &str) -> String {
+    if image.starts_with('/') || image.contains("://") {
+        return image.to_string();
+    }
+
+    match path.rfind('/') {
+        Some(end) => format!("{}{}", &path[..=end], image),
+        None => image.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip_frames(sheet: &Spritesheet, clip: &str) -> Vec<String> {
+        sheet.clips[clip].frames.iter().map(|frame| frame.sprite.clone()).collect()
+    }
+
+    #[test]
+    fn hash_frames_keep_file_order_for_frame_tags() {
+        let json = r#"{
+            "frames": {
+                "zeppelin 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "duration": 100 },
+                "biplane 1.aseprite": { "frame": { "x": 8, "y": 0, "w": 8, "h": 8 }, "duration": 50 },
+                "monoplane 2.aseprite": { "frame": { "x": 16, "y": 0, "w": 8, "h": 8 }, "duration": 200 },
+                "autogyro 3.aseprite": { "frame": { "x": 24, "y": 0, "w": 8, "h": 8 }, "duration": 100 }
+            },
+            "meta": {
+                "image": "planes.png",
+                "frameTags": [
+                    { "name": "takeoff", "from": 0, "to": 1, "direction": "forward" },
+                    { "name": "cruise", "from": 1, "to": 3, "direction": "pingpong" },
+                    { "name": "landing", "from": 2, "to": 3, "direction": "reverse" }
+                ]
+            }
+        }"#;
+
+        let sheet = read(json, "/assets/anim/planes.json").unwrap();
+
+        assert_eq!(sheet.images, vec!["/assets/anim/planes.png".to_string()]);
+        assert_eq!(clip_frames(&sheet, "takeoff"), ["zeppelin 0.aseprite", "biplane 1.aseprite"]);
+        assert_eq!(
+            clip_frames(&sheet, "cruise"),
+            ["biplane 1.aseprite", "monoplane 2.aseprite", "autogyro 3.aseprite", "monoplane 2.aseprite"]
+        );
+        assert_eq!(clip_frames(&sheet, "landing"), ["autogyro 3.aseprite", "monoplane 2.aseprite"]);
+        assert_eq!(sheet.clips["takeoff"].frames[1].duration, 0.05);
+    }
+
+    #[test]
+    fn rotated_trimmed_frames_keep_their_untrimmed_size() {
+        let json = r#"{
+            "frames": [{
+                "filename": "plane.png",
+                "frame": { "x": 10, "y": 20, "w": 88, "h": 73 },
+                "rotated": true,
+                "trimmed": true,
+                "spriteSourceSize": { "x": 5, "y": 6, "w": 88, "h": 73 },
+                "sourceSize": { "w": 98, "h": 83 },
+                "pivot": { "x": 0.25, "y": 0.75 }
+            }],
+            "meta": { "image": "/assets/packed.png" }
+        }"#;
+
+        let sheet = read(json, "/assets/packed.json").unwrap();
+        let sprite = &sheet.tileset["plane.png"];
+
+        assert_eq!(sprite.rect, Rect { x: 10, y: 20, width: 73, height: 88 });
+        assert_eq!((sprite.width(), sprite.height()), (98, 83));
+        assert_eq!(sprite.pivot, Some(Position { x: 0.25, y: 0.75 }));
+        assert_eq!(sheet.images, vec!["/assets/packed.png".to_string()]);
+    }
+
+    #[test]
+    fn plain_sheets_still_read() {
+        let json = r#"{ "image": "/assets/sheet.png", "tileset": { "a.png": { "x": 1, "y": 2, "width": 3, "height": 4 } } }"#;
+
+        let sheet = read(json, "/assets/sheet.json").unwrap();
+
+        assert_eq!(sheet.tileset["a.png"].rect, Rect { x: 1, y: 2, width: 3, height: 4 });
+        assert!(sheet.clips.is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+//! Traces atlas sprites into collider outlines for `World`.
+//!
+//! Usage: `trace_colliders <sheet.json> <sheet.png> <colliders.json> [entry...]`
+//!
+//! The sheet may be in any layout `atlas::read` takes. Without explicit entries every rock
+//! and plane sprite in the sheet is traced.
+
+use std::{collections::BTreeMap, env, fs, process};
+
+use anyhow::{anyhow, Result};
+use game::{atlas, engine::{Rect, SheetSprite}, outline, software::Bitmap};
+
+const ALPHA_THRESHOLD: u8 = 128;
+const TOLERANCE: f64 = 1.5;
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{:#}", err);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [sheet_path, image_path, output_path, entries @ ..] = args.as_slice() else {
+        return Err(anyhow!("Usage: trace_colliders <sheet.json> <sheet.png> <colliders.json> [entry...]"));
+    };
+
+    let sheet = atlas::read(&fs::read_to_string(sheet_path)?, sheet_path)?;
+    let bitmap = Bitmap::from_png(&fs::read(image_path)?)?;
+
+    let entries: Vec<String> = if entries.is_empty() {
+        sheet.tileset
+            .keys()
+            .filter(|name| name.starts_with("rock") || name.starts_with("plane"))
+            .cloned()
+            .collect()
+    } else {
+        entries.to_vec()
+    };
+
+    let mut outlines = BTreeMap::new();
+    for name in entries {
+        let sprite = sheet.tileset
+            .get(&name)
+            .ok_or_else(|| anyhow!("{} is not in {}", name, sheet_path))?;
+        let sprite = upright(&bitmap, sprite);
+        let whole = Rect { x: 0, y: 0, width: sprite.width as i32, height: sprite.height as i32 };
+        let polygon = outline::trace(&sprite, &whole, ALPHA_THRESHOLD, TOLERANCE);
+        println!("{}: {} vertices", name, polygon.len());
+        outlines.insert(name, polygon);
+    }
+
+    // One entry per line keeps regenerated files readable in a diff
+    let lines = outlines
+        .iter()
+        .map(|(name, polygon)| Ok(format!("  {}: {}", serde_json::to_string(name)?, serde_json::to_string(polygon)?)))
+        .collect::<Result<Vec<String>>>()?;
+    fs::write(output_path, format!("{{\n{}\n}}\n", lines.join(",\n")))?;
+    Ok(())
+}
+
+/// Cuts `sprite` out of the atlas whole and upright, the way the renderer draws it, so the
+/// outline is in pixels from the untrimmed top-left corner.
+fn upright(atlas: &Bitmap, sprite: &SheetSprite) -> Bitmap {
+    let mut bitmap = Bitmap::new(sprite.width() as u32, sprite.height() as u32);
+    let (offset_x, offset_y) = sprite.trim.as_ref().map_or((0, 0), |trim| (trim.x, trim.y));
+    let Rect { x, y, width, height } = sprite.rect;
+
+    for row in 0..height {
+        for column in 0..width {
+            // Rotated sprites are packed a quarter turn clockwise, the packed left column is the upright bottom row
+            let (upright_x, upright_y) = if sprite.rotated { (row, width - 1 - column) } else { (column, row) };
+            let color = atlas.pixel((x + column) as u32, (y + row) as u32);
+            bitmap.set_pixel((offset_x + upright_x) as u32, (offset_y + upright_y) as u32, color);
+        }
+    }
+    bitmap
+}
@@ -0,0 +1,144 @@
+use std::f64::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+/// Vertical wind pushing the plane up and down as it flies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Gust {
+    /// Peak acceleration in px/s².
+    pub strength: f64,
+    /// Distance scrolled in px for one full up and down cycle.
+    pub wavelength: f64,
+}
+
+impl Gust {
+    /// Acceleration at `distance`, positive is down like gravity.
+    pub fn acceleration(&self, distance: f64) -> f64 {
+        self.strength * (distance / self.wavelength * TAU).sin()
+    }
+}
+
+/// Sprites and air of one stretch of the course.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Biome {
+    pub name: String,
+    pub ground: String,
+    pub ceiling: String,
+    pub rock: String,
+    pub rock_down: String,
+    /// Linear damping on the plane, the higher the thicker the air.
+    #[serde(default)]
+    pub drag: f64,
+    #[serde(default)]
+    pub gust: Option<Gust>,
+}
+
+/// Biomes visited in order during a run, starting over after the last one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BiomeCycle {
+    /// Distance scrolled in px before moving on to the next biome.
+    pub length: f64,
+    /// Distance in px over which the terrain of a biome fades into the next one.
+    pub crossfade: f64,
+    pub biomes: Vec<Biome>,
+}
+
+/// Terrain to draw at some distance: `to` is drawn over `from` with `amount` opacity.
+/// Both are indices into `BiomeCycle::biomes`.
+pub struct BiomeBlend {
+    pub from: usize,
+    pub to: usize,
+    pub amount: f64,
+}
+
+impl Default for BiomeCycle {
+    fn default() -> Self {
+        let biome = |name: &str, ground: &str, ceiling: &str, rock: &str, drag, gust| Biome {
+            name: name.to_string(),
+            ground: format!("ground{}.png", ground),
+            ceiling: format!("ground{}.png", ceiling),
+            rock: format!("{}.png", rock),
+            rock_down: format!("{}Down.png", rock),
+            drag,
+            gust,
+        };
+
+        BiomeCycle {
+            length: 4_000.0,
+            crossfade: 400.0,
+            biomes: vec![
+                biome("Grass", "Grass", "Dirt", "rockGrass", 0.0, None),
+                biome("Rock", "Rock", "Rock", "rock", 0.0, None),
+                biome("Snow", "Snow", "Snow", "rockSnow", 0.1, None),
+                biome("Ice", "Ice", "Ice", "rockIce", 0.0, Some(Gust { strength: 30.0, wavelength: 600.0 })),
+            ],
+        }
+    }
+}
+
+impl BiomeCycle {
+    pub fn at(&self, distance: f64) -> BiomeBlend {
+        let stretch = (distance / self.length).floor().max(0.0) as usize;
+        let within = distance - stretch as f64 * self.length;
+        let amount = if self.crossfade > 0.0 {
+            ((within - (self.length - self.crossfade)) / self.crossfade).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        BiomeBlend {
+            from: stretch % self.biomes.len(),
+            to: (stretch + 1) % self.biomes.len(),
+            amount,
+        }
+    }
+
+    /// Index of the biome that owns the screen at `distance`, new rocks and the plane's air follow it.
+    pub fn dominant_index(&self, distance: f64) -> usize {
+        let blend = self.at(distance);
+        if blend.amount < 0.5 { blend.from } else { blend.to }
+    }
+
+    pub fn dominant(&self, distance: f64) -> &Biome {
+        &self.biomes[self.dominant_index(distance)]
+    }
+
+    pub fn max_drag(&self) -> f64 {
+        self.biomes.iter().map(|biome| biome.drag).fold(0.0, f64::max)
+    }
+
+    pub fn max_gust(&self) -> f64 {
+        self.biomes
+            .iter()
+            .filter_map(|biome| biome.gust.as_ref())
+            .map(|gust| gust.strength.abs())
+            .fold(0.0, f64::max)
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.biomes.is_empty() {
+            problems.push(String::from("biomes.biomes needs at least one biome"));
+        }
+        if self.length <= 0.0 {
+            problems.push(String::from("biomes.length must be positive"));
+        }
+        if self.crossfade < 0.0 || self.crossfade > self.length {
+            problems.push(String::from("biomes.crossfade must be between 0 and biomes.length"));
+        }
+
+        for biome in self.biomes.iter() {
+            if biome.drag < 0.0 {
+                problems.push(format!("biome {}: drag must not be negative", biome.name));
+            }
+            if biome.gust.as_ref().is_some_and(|gust| gust.wavelength <= 0.0) {
+                problems.push(format!("biome {}: gust.wavelength must be positive", biome.name));
+            }
+        }
+
+        problems
+    }
+}
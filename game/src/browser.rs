@@ -1,9 +1,13 @@
 use anyhow::{anyhow, Result};
 use futures::Future;
+use js_sys::Uint8Array;
 use wasm_bindgen::{closure::Closure, JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{Window, Document, HtmlImageElement, CanvasRenderingContext2d, HtmlCanvasElement, Response};
+use web_sys::{Window, Document, Gamepad, GamepadButton, HtmlImageElement, CanvasRenderingContext2d, HtmlCanvasElement, Response, Storage};
 
+use crate::{engine::Position, scores::{ScoreEntry, ScoreStore}};
+
+#[allow(unused_macros)]
 macro_rules! log {
     ( $($t:tt)* ) => {
         web_sys::console::log_1(&format!($($t)*).into());
@@ -28,8 +32,35 @@ pub fn canvas() -> Result<HtmlCanvasElement> {
         .map_err(|element| anyhow!("Error converting {:#?} to HtmlCanvasElement", element))
 }
 
+/// Maps a point from client coordinates to canvas pixels, whatever size CSS gives the canvas.
+pub fn canvas_position(canvas: &HtmlCanvasElement, client_x: f64, client_y: f64) -> Position {
+    let bounds = canvas.get_bounding_client_rect();
+    let scale_x = if bounds.width() > 0.0 { canvas.width() as f64 / bounds.width() } else { 1.0 };
+    let scale_y = if bounds.height() > 0.0 { canvas.height() as f64 / bounds.height() } else { 1.0 };
+
+    Position {
+        x: (client_x - bounds.left()) * scale_x,
+        y: (client_y - bounds.top()) * scale_y,
+    }
+}
+
+/// Canvas that is never added to the page, to draw into before copying onto the real one.
+pub fn new_canvas(width: u32, height: u32) -> Result<HtmlCanvasElement> {
+    let canvas = document()?
+        .create_element("canvas")
+        .map_err(|err| anyhow!("Could not create canvas {:#?}", err))?
+        .dyn_into::<HtmlCanvasElement>()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlCanvasElement", element))?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    Ok(canvas)
+}
+
 pub fn context() -> Result<CanvasRenderingContext2d> {
-    let canvas = canvas()?;
+    context_of(&canvas()?)
+}
+
+pub fn context_of(canvas: &HtmlCanvasElement) -> Result<CanvasRenderingContext2d> {
     let res_context = canvas
         .get_context("2d")
         .map_err(|err| anyhow!("Error getting 2d context {:#?}", err))?;
@@ -68,6 +99,64 @@ pub async fn fetch_json(json_path: &str) -> Result<JsValue> {
         .map_err(|err| anyhow!("error fetching JSON {:#?}", err))
 }
 
+pub async fn fetch_text(resource: &str) -> Result<String> {
+    let value = fetch_with_str(resource).await?;
+    let data: Response = value.dyn_into()
+        .map_err(|element| anyhow!("Error converting {:#?} to Response", element))?;
+
+    JsFuture::from(
+        data
+        .text()
+        .map_err(|err| anyhow!("Could not get text from response {:#?}", err))?
+    )
+        .await
+        .map_err(|err| anyhow!("error fetching text {:#?}", err))?
+        .as_string()
+        .ok_or_else(|| anyhow!("Response body of {} is not a string", resource))
+}
+
+pub async fn fetch_bytes(resource: &str) -> Result<Vec<u8>> {
+    let value = fetch_with_str(resource).await?;
+    let data: Response = value.dyn_into()
+        .map_err(|element| anyhow!("Error converting {:#?} to Response", element))?;
+
+    let buffer = JsFuture::from(
+        data
+        .array_buffer()
+        .map_err(|err| anyhow!("Could not get bytes from response {:#?}", err))?
+    )
+        .await
+        .map_err(|err| anyhow!("error fetching bytes {:#?}", err))?;
+
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+/// Indices of the buttons held on any connected gamepad.
+pub fn gamepad_buttons() -> Vec<u32> {
+    let Ok(gamepads) = window().and_then(|window| {
+        window
+            .navigator()
+            .get_gamepads()
+            .map_err(|err| anyhow!("Could not get gamepads {:#?}", err))
+    }) else {
+        return Vec::new();
+    };
+
+    gamepads
+        .iter()
+        .filter_map(|gamepad| gamepad.dyn_into::<Gamepad>().ok())
+        .flat_map(|gamepad| {
+            gamepad
+                .buttons()
+                .iter()
+                .enumerate()
+                .filter(|(_, button)| button.dyn_ref::<GamepadButton>().is_some_and(|button| button.pressed()))
+                .map(|(index, _)| index as u32)
+                .collect::<Vec<u32>>()
+        })
+        .collect()
+}
+
 pub fn new_image() -> Result<HtmlImageElement> {
     HtmlImageElement::new().map_err(|err| anyhow!("Could not create HtmlImageElement: {:#?}", err))
 }
@@ -85,3 +174,43 @@ pub fn create_animation_closure(f: impl FnMut(f64) + 'static) -> Closure<dyn FnM
 pub fn now() -> Result<f64> {
     Ok(window()?.performance().ok_or_else(|| anyhow!("Performance object not found"))?.now())
 }
+
+pub fn local_storage() -> Result<Storage> {
+    window()?
+        .local_storage()
+        .map_err(|err| anyhow!("Error accessing localStorage {:#?}", err))?
+        .ok_or_else(|| anyhow!("No localStorage found"))
+}
+
+pub struct LocalStorageStore {
+    key: String,
+}
+
+impl LocalStorageStore {
+    pub fn new(key: &str) -> Self {
+        LocalStorageStore { key: key.to_string() }
+    }
+}
+
+impl ScoreStore for LocalStorageStore {
+    fn load(&self) -> Result<Vec<ScoreEntry>> {
+        let value = local_storage()?
+            .get_item(&self.key)
+            .map_err(|err| anyhow!("Error reading {} from localStorage {:#?}", self.key, err))?;
+
+        match value {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|err| anyhow!("Could not parse high scores: {}", err)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn save(&mut self, entries: &[ScoreEntry]) -> Result<()> {
+        let json = serde_json::to_string(entries)
+            .map_err(|err| anyhow!("Could not serialize high scores: {}", err))?;
+
+        local_storage()?
+            .set_item(&self.key, &json)
+            .map_err(|err| anyhow!("Error writing {} to localStorage {:#?}", self.key, err))
+    }
+}
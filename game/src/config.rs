@@ -0,0 +1,233 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{biome::BiomeCycle, difficulty::DifficultyCurve, utils::Dimension};
+
+/// Extra lift while the flap button stays down, so longer presses climb higher.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlapHold {
+    /// Vertical acceleration in px/s² while held, negative is up.
+    pub acceleration: f64,
+    /// Longest a press keeps lifting, in seconds.
+    pub duration: f64,
+}
+
+/// How far the plane pitches with its vertical speed, angles are in degrees.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tilt {
+    /// Steepest nose-up angle, reached while climbing.
+    pub max_climb: f64,
+    /// Steepest nose-down angle, reached while falling.
+    pub max_dive: f64,
+    /// Vertical speed in px/s at which either limit is reached.
+    pub full_tilt_speed: f64,
+    /// How quickly the angle catches up with the speed, the fraction left after one
+    /// second is `e^-easing`.
+    pub easing: f64,
+}
+
+impl Default for Tilt {
+    fn default() -> Self {
+        Tilt { max_climb: 25.0, max_dive: 60.0, full_tilt_speed: 300.0, easing: 10.0 }
+    }
+}
+
+impl Tilt {
+    /// Angle in radians the plane settles at when moving down at `velocity` px/s,
+    /// positive turns clockwise so the nose drops.
+    pub fn target(&self, velocity: f64) -> f64 {
+        let amount = (velocity / self.full_tilt_speed).clamp(-1.0, 1.0);
+        let limit = if amount < 0.0 { self.max_climb } else { self.max_dive };
+        (amount * limit).to_radians()
+    }
+
+    /// Moves `angle` towards the target for `velocity` over `delta` seconds.
+    pub fn ease(&self, angle: f64, velocity: f64, delta: f64) -> f64 {
+        angle + (self.target(velocity) - angle) * (1.0 - (-self.easing * delta).exp())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhysicsConfig {
+    /// Downwards acceleration in px/s².
+    pub gravity: f64,
+    /// Vertical impulse of a single flap, negative is up.
+    pub flap_impulse: f64,
+    /// Seconds after a flap during which new presses are ignored.
+    pub flap_cooldown: f64,
+    /// Stops the plane before every flap, so each one climbs the same however fast it fell.
+    pub flap_resets_velocity: bool,
+    #[serde(default)]
+    pub flap_hold: Option<FlapHold>,
+    #[serde(default)]
+    pub tilt: Tilt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Medal {
+    Bronze,
+    Silver,
+    Gold,
+}
+
+/// A plane colour, unlocked once the best score reaches `unlock_score`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Skin {
+    pub color: String,
+    pub unlock_score: u32,
+    /// Sprites of the three propeller frames, `plane<color>1.png` to `plane<color>3.png`
+    /// of the main sheet when left out.
+    #[serde(default)]
+    pub frames: Option<[String; 3]>,
+    /// Clip of the spritesheet to fly with instead of cycling through `frames`. The
+    /// collider still comes from the first of `frames`.
+    #[serde(default)]
+    pub animation: Option<String>,
+}
+
+impl Skin {
+    pub fn frame_names(&self) -> [String; 3] {
+        self.frames
+            .clone()
+            .unwrap_or_else(|| [1, 2, 3].map(|frame| format!("plane{}{}.png", self.color, frame)))
+    }
+
+    pub fn is_unlocked(&self, best: Option<u32>) -> bool {
+        self.unlock_score == 0 || best.is_some_and(|best| best >= self.unlock_score)
+    }
+}
+
+/// Minimum score for each medal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MedalThresholds {
+    pub bronze: u32,
+    pub silver: u32,
+    pub gold: u32,
+}
+
+impl Default for MedalThresholds {
+    fn default() -> Self {
+        MedalThresholds { bronze: 10, silver: 25, gold: 50 }
+    }
+}
+
+impl MedalThresholds {
+    pub fn award(&self, score: u32) -> Option<Medal> {
+        if score >= self.gold {
+            Some(Medal::Gold)
+        } else if score >= self.silver {
+            Some(Medal::Silver)
+        } else if score >= self.bronze {
+            Some(Medal::Bronze)
+        } else {
+            None
+        }
+    }
+}
+
+/// Tuning values loaded from `/assets/config.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GameConfig {
+    pub canvas: Dimension,
+    /// Height of the ground and ceiling strips the plane must not touch.
+    pub ground_height: f64,
+    /// Seconds spent in `GetReady` before the run starts.
+    pub countdown: f64,
+    /// Horizontal speed in px/s at a scroll speed multiplier of 1.
+    pub scroll_speed: f64,
+    pub difficulty: DifficultyCurve,
+    pub biomes: BiomeCycle,
+    pub physics: PhysicsConfig,
+    pub medals: MedalThresholds,
+    /// Planes offered in the hangar, in the order they cycle through.
+    pub skins: Vec<Skin>,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            canvas: Dimension { width: 800, height: 480 },
+            ground_height: 71.0,
+            countdown: 4.0,
+            scroll_speed: 100.0,
+            difficulty: DifficultyCurve::default(),
+            biomes: BiomeCycle::default(),
+            physics: PhysicsConfig {
+                gravity: 9.81 * 10.0 * 2.0,
+                flap_impulse: -740_000.0,
+                flap_cooldown: 0.15,
+                flap_resets_velocity: true,
+                flap_hold: None,
+                tilt: Tilt::default(),
+            },
+            medals: MedalThresholds::default(),
+            skins: [("Red", 0), ("Blue", 10), ("Green", 25), ("Yellow", 50)]
+                .into_iter()
+                .map(|(color, unlock_score)| Skin { color: color.to_string(), unlock_score, frames: None, animation: None })
+                .collect(),
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let config: GameConfig = serde_json::from_str(json)
+            .map_err(|err| anyhow!("Could not parse game config: {}", err))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks every value at once so a designer sees all the problems in one go.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        let mut check = |valid: bool, problem: &str| {
+            if !valid {
+                problems.push(problem.to_string());
+            }
+        };
+
+        check(self.canvas.width > 0 && self.canvas.height > 0, "canvas must have a positive width and height");
+        check(
+            self.ground_height >= 0.0 && self.ground_height * 2.0 < self.canvas.height as f64,
+            "ground_height must leave room between the ground and the ceiling"
+        );
+        check(self.countdown > 0.0, "countdown must be positive");
+        check(self.scroll_speed > 0.0, "scroll_speed must be positive");
+        check(self.physics.gravity.is_finite() && self.physics.gravity > 0.0, "physics.gravity must be positive");
+        check(self.physics.flap_impulse.is_finite() && self.physics.flap_impulse < 0.0, "physics.flap_impulse must be negative (upwards)");
+        check(self.physics.flap_cooldown >= 0.0, "physics.flap_cooldown must not be negative");
+        if let Some(hold) = self.physics.flap_hold.as_ref() {
+            check(hold.acceleration.is_finite() && hold.acceleration < 0.0, "physics.flap_hold.acceleration must be negative (upwards)");
+            check(hold.duration > 0.0, "physics.flap_hold.duration must be positive");
+        }
+        let tilt = &self.physics.tilt;
+        check(
+            (0.0..=90.0).contains(&tilt.max_climb) && (0.0..=90.0).contains(&tilt.max_dive),
+            "physics.tilt.max_climb and physics.tilt.max_dive must be between 0 and 90 degrees"
+        );
+        check(tilt.full_tilt_speed > 0.0, "physics.tilt.full_tilt_speed must be positive");
+        check(tilt.easing > 0.0, "physics.tilt.easing must be positive");
+        check(
+            self.medals.bronze <= self.medals.silver && self.medals.silver <= self.medals.gold,
+            "medals must satisfy bronze <= silver <= gold"
+        );
+        check(
+            self.skins.iter().any(|skin| skin.unlock_score == 0),
+            "skins must include one with an unlock_score of 0"
+        );
+        problems.extend(self.biomes.problems());
+        problems.extend(self.difficulty.problems());
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Invalid game config:\n - {}", problems.join("\n - ")))
+        }
+    }
+}
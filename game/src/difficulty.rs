@@ -0,0 +1,386 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utils::Dimension;
+
+pub const ROCK_SIZE: Dimension = Dimension { width: 108, height: 239 };
+pub const PLANE_SIZE: Dimension = Dimension { width: 88, height: 73 };
+/// Left edge of the plane, which never moves horizontally.
+pub const PLANE_X: f64 = 88.0;
+
+/// How far rocks are pushed out of the screen, negative values push them further in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OffsetRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// One keyframe of the curve, or the curve sampled at `distance`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Difficulty {
+    /// Distance scrolled in px since the run started.
+    pub distance: f64,
+    /// Multiplier applied to the configured scroll speed.
+    pub scroll_speed: f64,
+    pub distance_between_obstacles: f64,
+    pub vertical_offset: OffsetRange,
+}
+
+/// Keyframes sorted by distance, linearly interpolated in between and held past the last one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DifficultyCurve {
+    keyframes: Vec<Difficulty>,
+}
+
+impl Default for DifficultyCurve {
+    fn default() -> Self {
+        let keyframe = |distance, scroll_speed, distance_between_obstacles, min| Difficulty {
+            distance,
+            scroll_speed,
+            distance_between_obstacles,
+            vertical_offset: OffsetRange { min, max: ROCK_SIZE.height as f64/2.0 },
+        };
+
+        DifficultyCurve::new(vec![
+            keyframe(0.0, 1.0, 400.0, 0.0),
+            keyframe(5_000.0, 1.15, 390.0, -10.0),
+            keyframe(12_000.0, 1.25, 380.0, -15.0),
+            keyframe(25_000.0, 1.3, 380.0, -20.0),
+        ])
+    }
+}
+
+impl DifficultyCurve {
+    pub fn new(keyframes: Vec<Difficulty>) -> Self {
+        DifficultyCurve { keyframes }
+    }
+
+    pub fn keyframes(&self) -> &[Difficulty] {
+        &self.keyframes
+    }
+
+    pub fn at(&self, distance: f64) -> Difficulty {
+        let next = self.keyframes.iter().position(|keyframe| keyframe.distance > distance);
+        let (from, to) = match next {
+            Some(0) => return Difficulty { distance, ..self.keyframes[0] },
+            Some(index) => (&self.keyframes[index - 1], &self.keyframes[index]),
+            None => return Difficulty { distance, ..self.keyframes[self.keyframes.len() - 1] },
+        };
+
+        let t = (distance - from.distance) / (to.distance - from.distance);
+        let lerp = |from: f64, to: f64| from + (to - from) * t;
+
+        Difficulty {
+            distance,
+            scroll_speed: lerp(from.scroll_speed, to.scroll_speed),
+            distance_between_obstacles: lerp(from.distance_between_obstacles, to.distance_between_obstacles),
+            vertical_offset: OffsetRange {
+                min: lerp(from.vertical_offset.min, to.vertical_offset.min),
+                max: lerp(from.vertical_offset.max, to.vertical_offset.max),
+            },
+        }
+    }
+
+    /// Lists what is wrong with the curve itself. Whether its stages can be flown is only
+    /// checked by the tests, for the default and the shipped config.
+    pub fn problems(&self) -> Vec<String> {
+        if self.keyframes.is_empty() {
+            return vec![String::from("difficulty needs at least one keyframe")];
+        }
+
+        let mut problems = Vec::new();
+        if self.keyframes.windows(2).any(|pair| pair[0].distance >= pair[1].distance) {
+            problems.push(String::from("difficulty keyframes must be sorted by increasing distance"));
+        }
+
+        let min_spacing = (ROCK_SIZE.width + PLANE_SIZE.width) as f64;
+        for keyframe in self.keyframes.iter() {
+            let at = keyframe.distance;
+            if keyframe.scroll_speed <= 0.0 {
+                problems.push(format!("difficulty at {}: scroll_speed must be positive", at));
+            }
+            if keyframe.distance_between_obstacles <= min_spacing {
+                problems.push(format!("difficulty at {}: distance_between_obstacles must be over {}", at, min_spacing));
+            }
+            if keyframe.vertical_offset.min > keyframe.vertical_offset.max {
+                problems.push(format!("difficulty at {}: vertical_offset.min must not exceed vertical_offset.max", at));
+            }
+            if keyframe.vertical_offset.max > ROCK_SIZE.height as f64 {
+                problems.push(format!("difficulty at {}: vertical_offset.max would push rocks off the screen", at));
+            }
+        }
+
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GameConfig, Tilt};
+    use std::collections::HashMap;
+
+    /// How many alternating rocks the winnability check flies through per stage.
+    const WINNABILITY_OBSTACLES: usize = 6;
+
+    /// Every keyframe plus the halfway points between them.
+    fn stages(curve: &DifficultyCurve) -> Vec<Difficulty> {
+        let mut stages: Vec<Difficulty> = Vec::new();
+        for (index, keyframe) in curve.keyframes.iter().enumerate() {
+            if index > 0 {
+                let previous = &curve.keyframes[index - 1];
+                stages.push(curve.at((previous.distance + keyframe.distance) / 2.0));
+            }
+            stages.push(*keyframe);
+        }
+        stages
+    }
+
+    /// Lists the stages of `config` the plane cannot get through with its physics.
+    fn unwinnable(config: &GameConfig) -> Vec<String> {
+        stages(&config.difficulty)
+            .iter()
+            .filter_map(|stage| check_winnable(stage, config).err())
+            .map(|problem| format!("difficulty at {}: {}", problem.0, problem.1))
+            .collect()
+    }
+
+    /// Flies the plane with the real gravity, flaps, tilt and tick rate through
+    /// rocks that alternate between the ground and the ceiling, as close together and as
+    /// far into the screen as `stage` allows, in the thickest air of any biome and against
+    /// its strongest gust the whole way, trying every sequence of taps. The plane
+    /// crashes once the box around it, turned as far as it is tilted, touches a rock, the
+    /// ground or the ceiling, which is never less than the traced outline covers. It never
+    /// holds a flap, so a configured hold only adds slack. Rocks are treated as boxes, so
+    /// the real, pointed rocks leave some slack too.
+    fn check_winnable(stage: &Difficulty, config: &GameConfig) -> Result<(), (f64, String)> {
+        let fail = |problem: &str| Err((stage.distance, problem.to_string()));
+
+        let dt = 1.0 / crate::TICK_RATE;
+        let physics = &config.physics;
+        let gravity = physics.gravity;
+        let flap = -physics.flap_impulse / (PLANE_SIZE.width * PLANE_SIZE.height) as f64;
+        let cooldown = physics.flap_cooldown.max(dt);
+        let gust = config.biomes.max_gust();
+        if flap <= (gravity + gust) * cooldown {
+            return fail("flapping as often as the cooldown allows cannot overcome gravity");
+        }
+        if gravity - gust <= 0.0 {
+            return fail("gusts can hold the plane up against gravity");
+        }
+
+        // Top and bottom edge of every opening
+        let canvas_height = config.canvas.height as f64;
+        let rock_height = ROCK_SIZE.height as f64;
+        let below_ceiling_rock = (rock_height - stage.vertical_offset.min, canvas_height - config.ground_height);
+        let above_ground_rock = (config.ground_height, canvas_height - rock_height + stage.vertical_offset.min);
+        let narrowest = (below_ceiling_rock.1 - below_ceiling_rock.0).min(above_ground_rock.1 - above_ground_rock.0);
+        if narrowest < PLANE_SIZE.height as f64 {
+            return fail("the openings are narrower than the plane");
+        }
+
+        let speed = config.scroll_speed * stage.scroll_speed;
+        let course = Course {
+            open_air: (config.ground_height, canvas_height - config.ground_height),
+            rocks: (0..WINNABILITY_OBSTACLES)
+                .map(|index| (
+                    config.canvas.width as f64 + index as f64 * stage.distance_between_obstacles,
+                    if index % 2 == 0 { above_ground_rock } else { below_ceiling_rock },
+                ))
+                .collect(),
+            speed,
+            dt,
+            gravity,
+            gust,
+            drag: config.biomes.max_drag(),
+            flap,
+            cooldown,
+            resets_velocity: physics.flap_resets_velocity,
+            tilt: physics.tilt.clone(),
+        };
+
+        let flight = Flight { tick: 0, y: canvas_height / 2.0, velocity: 0.0, angle: 0.0, since_flap: cooldown };
+        if course.fly(flight) { Ok(()) } else { fail("the plane cannot get from one opening to the next in time") }
+    }
+
+    enum Step {
+        Flying,
+        Through,
+        Crashed,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    struct Flight {
+        tick: u32,
+        y: f64,
+        velocity: f64,
+        /// Tilt in radians, eased like the real plane's.
+        angle: f64,
+        since_flap: f64,
+    }
+
+    impl Flight {
+        /// Half the width and height of the box around the plane turned by its tilt.
+        fn half_extents(&self) -> (f64, f64) {
+            let (sin, cos) = (self.angle.sin().abs(), self.angle.cos().abs());
+            let (width, height) = (PLANE_SIZE.width as f64, PLANE_SIZE.height as f64);
+            ((width * cos + height * sin) / 2.0, (width * sin + height * cos) / 2.0)
+        }
+    }
+
+    /// The rocks of one winnability check and the physics flying through them.
+    ///
+    /// Rather than guess what a player would tap, `fly` follows every sequence of taps at
+    /// once. Flights that end up within a few pixels, px/s and degrees of each other, with
+    /// the same time since their last tap, are flown on as one.
+    struct Course {
+        /// Left edge at the first tick and the top and bottom edge of the opening.
+        rocks: Vec<(f64, (f64, f64))>,
+        /// Where the ceiling ends and the ground starts.
+        open_air: (f64, f64),
+        speed: f64,
+        dt: f64,
+        gravity: f64,
+        gust: f64,
+        drag: f64,
+        /// Vertical speed a flap gives, in px/s.
+        flap: f64,
+        cooldown: f64,
+        resets_velocity: bool,
+        tilt: Tilt,
+    }
+
+    impl Course {
+        fn rock_x(&self, index: usize, tick: u32) -> f64 {
+            self.rocks[index].0 - self.speed * self.dt * tick as f64
+        }
+
+        /// Middle of the next opening the plane has to get through, `None` once every rock is behind.
+        fn target(&self, flight: &Flight) -> Option<f64> {
+            let (top, bottom) = (0..self.rocks.len())
+                .find(|&index| self.rock_x(index, flight.tick) + ROCK_SIZE.width as f64 >= PLANE_X)
+                .map(|index| self.rocks[index].1)?;
+
+            Some((top + bottom) / 2.0)
+        }
+
+        fn can_flap(&self, flight: &Flight) -> bool {
+            flight.since_flap + self.dt >= self.cooldown
+        }
+
+        /// Whether any sequence of taps gets the plane from `flight` past every rock.
+        fn fly(&self, flight: Flight) -> bool {
+            let mut flights = vec![flight];
+            while !flights.is_empty() {
+                let mut next = HashMap::new();
+                for flight in &flights {
+                    for flap in [false, true] {
+                        if flap && !self.can_flap(flight) {
+                            continue;
+                        }
+                        let mut flight = *flight;
+                        match self.step(&mut flight, flap) {
+                            Step::Flying => {
+                                let key = (
+                                    (flight.y / 4.0) as i64,
+                                    (flight.velocity / 20.0) as i64,
+                                    (flight.angle.to_degrees() / 10.0) as i64,
+                                    (flight.since_flap.min(self.cooldown) / self.dt) as i64,
+                                );
+                                next.entry(key).or_insert(flight);
+                            }
+                            Step::Through => return true,
+                            Step::Crashed => {}
+                        }
+                    }
+                }
+                flights = next.into_values().collect();
+            }
+            false
+        }
+
+        fn step(&self, flight: &mut Flight, flap: bool) -> Step {
+            let Some(target) = self.target(flight) else { return Step::Through };
+
+            flight.since_flap += self.dt;
+            if flap && flight.since_flap >= self.cooldown {
+                flight.since_flap = 0.0;
+                if self.resets_velocity {
+                    flight.velocity = 0.0;
+                }
+                flight.velocity -= self.flap;
+            }
+
+            // The gust always pushes the plane away from the opening
+            let against = if target > flight.y { -self.gust } else { self.gust };
+            flight.velocity += (self.gravity + against) * self.dt;
+            flight.velocity /= 1.0 + self.dt * self.drag;
+            flight.y += flight.velocity * self.dt;
+            flight.angle = self.tilt.ease(flight.angle, flight.velocity, self.dt);
+            flight.tick += 1;
+
+            let (half_width, half_height) = flight.half_extents();
+            let center_x = PLANE_X + PLANE_SIZE.width as f64 / 2.0;
+            let outside = |(top, bottom): (f64, f64)| flight.y - half_height < top || flight.y + half_height > bottom;
+            let crashed = outside(self.open_air) || (0..self.rocks.len()).any(|index| {
+                let x = self.rock_x(index, flight.tick);
+                x < center_x + half_width && x + ROCK_SIZE.width as f64 > center_x - half_width && outside(self.rocks[index].1)
+            });
+
+            if crashed { Step::Crashed } else { Step::Flying }
+        }
+    }
+
+    #[test]
+    fn default_config_is_winnable_at_every_stage() {
+        let config = GameConfig::default();
+        config.validate().unwrap();
+
+        assert_eq!(unwinnable(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn shipped_config_is_winnable_at_every_stage() {
+        let config = GameConfig::from_json(include_str!("../../static/assets/config.json")).unwrap();
+
+        assert_eq!(unwinnable(&config), Vec::<String>::new());
+    }
+
+    /// Where the plane's centre is when it falls onto the ground at 380 with `tilt`.
+    fn fall(tilt: Tilt) -> f64 {
+        let course = Course {
+            rocks: vec![(10_000.0, (0.0, 480.0))],
+            open_air: (100.0, 380.0),
+            speed: 100.0,
+            dt: 1.0 / 60.0,
+            gravity: 200.0,
+            gust: 0.0,
+            drag: 0.0,
+            flap: 300.0,
+            cooldown: 0.15,
+            resets_velocity: true,
+            tilt,
+        };
+        let mut flight = Flight { tick: 0, y: 240.0, velocity: 0.0, angle: 0.0, since_flap: 0.0 };
+
+        (0..600).find(|_| matches!(course.step(&mut flight, false), Step::Crashed)).expect("The plane never landed");
+        flight.y
+    }
+
+    #[test]
+    fn falling_onto_the_ground_between_rocks_is_a_crash() {
+        let level = fall(Tilt { max_climb: 0.0, max_dive: 0.0, ..Tilt::default() });
+
+        assert!(level + PLANE_SIZE.height as f64 / 2.0 > 380.0 && level < 350.0);
+    }
+
+    #[test]
+    fn diving_planes_reach_the_ground_sooner() {
+        let diving = fall(Tilt::default());
+
+        // Nose down, the turned box is taller than the level plane
+        assert!(diving + PLANE_SIZE.height as f64 / 2.0 < 380.0);
+    }
+}
@@ -1,15 +1,14 @@
-use std::{rc::Rc, cell::{RefCell, Cell}, sync::Mutex, collections::HashMap, io::BufRead};
+use std::{rc::Rc, cell::RefCell, sync::Mutex, collections::HashMap, f64::consts::FRAC_PI_2};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use serde::Deserialize;
-use wasm_bindgen::prelude::*;
-use web_sys::{CanvasRenderingContext2d, HtmlImageElement, MouseEvent, TouchEvent};
-use futures;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{prelude::*, Clamped};
+use web_sys::{CanvasRenderingContext2d, Event, HtmlCanvasElement, HtmlImageElement, ImageData, KeyboardEvent, MouseEvent, TouchEvent};
 
-use crate::browser;
+use crate::{browser, input::{Action, Binding, InputDevices, InputState}, replay::{Replay, SessionStart}, software::Bitmap};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
 pub struct Rect {
     pub x: i32,
     pub y: i32,
@@ -17,28 +16,308 @@ pub struct Rect {
     pub height: i32,
 }
 
-#[derive(Debug, Deserialize)]
+/// Where the packed pixels of a trimmed sprite sit in the sprite before trimming.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trim {
+    /// Offset of the packed pixels from the untrimmed top-left corner.
+    pub x: i32,
+    pub y: i32,
+    /// Untrimmed size.
+    pub width: i32,
+    pub height: i32,
+}
+
+/// One sprite of a `Spritesheet` and the atlas it is cut out of.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SheetSprite {
+    /// Index into `Spritesheet::images`.
+    pub atlas: usize,
+    /// Pixels in the atlas, as packed.
+    pub rect: Rect,
+    /// Packed turned a quarter clockwise, so `rect` is as tall as the sprite is wide.
+    pub rotated: bool,
+    /// Transparent edges the packer cut off, `None` when the sprite is whole.
+    pub trim: Option<Trim>,
+    /// Point the sprite is meant to be placed about, as a fraction of its untrimmed size.
+    pub pivot: Option<Position>,
+}
+
+impl SheetSprite {
+    /// Untrimmed width, the one to lay things out with.
+    pub fn width(&self) -> i32 {
+        match (&self.trim, self.rotated) {
+            (Some(trim), _) => trim.width,
+            (None, true) => self.rect.height,
+            (None, false) => self.rect.width,
+        }
+    }
+
+    /// Untrimmed height, the one to lay things out with.
+    pub fn height(&self) -> i32 {
+        match (&self.trim, self.rotated) {
+            (Some(trim), _) => trim.height,
+            (None, true) => self.rect.width,
+            (None, false) => self.rect.height,
+        }
+    }
+}
+
+/// One step of a `Clip`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipFrame<T> {
+    pub sprite: T,
+    /// Seconds the frame stays up.
+    pub duration: f64,
+}
+
+/// Frames shown one after the other for their own duration, looping. A sheet's clips
+/// name their sprites, `Sprites` resolves them once like any other sprite.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip<T = String> {
+    /// Never empty.
+    pub frames: Vec<ClipFrame<T>>,
+}
+
+impl<T> Clip<T> {
+    pub fn duration(&self) -> f64 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+
+    /// Frame up `time` seconds after the clip started.
+    pub fn frame_at(&self, time: f64) -> &T {
+        let duration = self.duration();
+        let mut time = if duration > 0.0 { time.rem_euclid(duration) } else { 0.0 };
+
+        for frame in self.frames.iter() {
+            if time < frame.duration {
+                return &frame.sprite;
+            }
+            time -= frame.duration;
+        }
+        &self.frames[self.frames.len() - 1].sprite
+    }
+
+    pub fn map<U>(&self, mut f: impl FnMut(&T) -> U) -> Clip<U> {
+        Clip {
+            frames: self.frames
+                .iter()
+                .map(|frame| ClipFrame { sprite: f(&frame.sprite), duration: frame.duration })
+                .collect(),
+        }
+    }
+}
+
+/// Named sprites of one or more atlas images. A sheet file lists its sprites either as
+/// a map, `{ "name": rect }`, or as an array, `[{ "name": "name", "rect": rect }]`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(try_from = "SheetFile")]
 pub struct Spritesheet {
-    pub image: String,
-    pub tileset: HashMap<String, Rect>,
+    /// Source of every atlas image, in the order the sheets were merged.
+    pub images: Vec<String>,
+    pub tileset: HashMap<String, SheetSprite>,
+    /// Animations by name, only the formats `atlas` imports have any.
+    pub clips: HashMap<String, Clip>,
 }
 
-pub struct Renderer {
+#[derive(Deserialize)]
+struct SheetFile {
+    image: String,
+    tileset: SheetEntries,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SheetEntries {
+    Map(HashMap<String, Rect>),
+    List(Vec<NamedRect>),
+}
+
+#[derive(Deserialize)]
+struct NamedRect {
+    name: String,
+    rect: Rect,
+}
+
+impl TryFrom<SheetFile> for Spritesheet {
+    type Error = String;
+
+    fn try_from(file: SheetFile) -> std::result::Result<Self, String> {
+        let entries: Vec<(String, Rect)> = match file.tileset {
+            SheetEntries::Map(map) => map.into_iter().collect(),
+            SheetEntries::List(list) => list.into_iter().map(|entry| (entry.name, entry.rect)).collect(),
+        };
+
+        let mut tileset = HashMap::new();
+        for (name, rect) in entries {
+            if tileset.insert(name.clone(), SheetSprite { rect, ..SheetSprite::default() }).is_some() {
+                return Err(format!("{} is listed twice in the sheet of {}", name, file.image));
+            }
+        }
+
+        Ok(Spritesheet { images: vec![file.image], tileset, clips: HashMap::new() })
+    }
+}
+
+impl Spritesheet {
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|err| anyhow!("Could not parse spritesheet: {}", err))
+    }
+
+    /// Adds the atlases, sprites and clips of `other`. Both sheets share one set of names, so
+    /// a name in both is an error rather than one sprite silently hiding the other.
+    pub fn merge(mut self, other: Spritesheet) -> Result<Self> {
+        let offset = self.images.len();
+        let clashes: Vec<&str> = other.tileset
+            .keys()
+            .filter(|name| self.tileset.contains_key(*name))
+            .chain(other.clips.keys().filter(|name| self.clips.contains_key(*name)))
+            .map(String::as_str)
+            .collect();
+        if !clashes.is_empty() {
+            return Err(anyhow!("Spritesheets both have sprites or clips named {}", clashes.join(", ")));
+        }
+
+        self.images.extend(other.images);
+        self.clips.extend(other.clips);
+        self.tileset.extend(
+            other.tileset
+                .into_iter()
+                .map(|(name, sprite)| (name, SheetSprite { atlas: sprite.atlas + offset, ..sprite }))
+        );
+        Ok(self)
+    }
+}
+
+pub enum Image {
+    Element(HtmlImageElement),
+    /// Pixels made at runtime, like rasterized glyphs, kept on an offscreen canvas.
+    Canvas(HtmlCanvasElement),
+    Bitmap(Bitmap),
+}
+
+pub trait RenderBackend {
+    fn clear(&self, rect: &Rect);
+    fn draw_sub_image(&self, image: &Image, orig: &Rect, dest: &Rect);
+    fn save(&self);
+    fn restore(&self);
+    fn translate(&self, x: f64, y: f64);
+    fn rotate(&self, angle: f64);
+    /// Stretches everything drawn afterwards, negative factors mirror it.
+    fn scale(&self, x: f64, y: f64);
+    /// Opacity applied to everything drawn afterwards, kept by `save` and `restore`.
+    fn set_global_alpha(&self, alpha: f64);
+    fn global_alpha(&self) -> f64;
+    /// Like `draw_sub_image`, with every pixel's color multiplied by `tint`.
+    fn draw_tinted_sub_image(&self, image: &Image, orig: &Rect, dest: &Rect, tint: [u8; 3]);
+    /// Turns pixels made at runtime into an image this backend can draw.
+    fn create_image(&self, bitmap: &Bitmap) -> Result<Image>;
+}
+
+impl<T: RenderBackend + ?Sized> RenderBackend for Rc<T> {
+    fn clear(&self, rect: &Rect) {
+        (**self).clear(rect);
+    }
+
+    fn draw_sub_image(&self, image: &Image, orig: &Rect, dest: &Rect) {
+        (**self).draw_sub_image(image, orig, dest);
+    }
+
+    fn save(&self) {
+        (**self).save();
+    }
+
+    fn restore(&self) {
+        (**self).restore();
+    }
+
+    fn translate(&self, x: f64, y: f64) {
+        (**self).translate(x, y);
+    }
+
+    fn rotate(&self, angle: f64) {
+        (**self).rotate(angle);
+    }
+
+    fn scale(&self, x: f64, y: f64) {
+        (**self).scale(x, y);
+    }
+
+    fn set_global_alpha(&self, alpha: f64) {
+        (**self).set_global_alpha(alpha);
+    }
+
+    fn global_alpha(&self) -> f64 {
+        (**self).global_alpha()
+    }
+
+    fn draw_tinted_sub_image(&self, image: &Image, orig: &Rect, dest: &Rect, tint: [u8; 3]) {
+        (**self).draw_tinted_sub_image(image, orig, dest, tint);
+    }
+
+    fn create_image(&self, bitmap: &Bitmap) -> Result<Image> {
+        (**self).create_image(bitmap)
+    }
+}
+
+pub struct CanvasBackend {
     pub context: CanvasRenderingContext2d,
+    /// Offscreen canvas tinted sprites are put together on, kept between draws.
+    scratch: RefCell<Option<HtmlCanvasElement>>,
 }
 
-impl Renderer {
-    pub fn clear(&self, rect: &Rect) {
-        self
-            .context
-            .clear_rect(rect.x.into(), rect.y.into(), rect.width.into(), rect.height.into());
+impl CanvasBackend {
+    pub fn new(context: CanvasRenderingContext2d) -> Self {
+        CanvasBackend { context, scratch: RefCell::new(None) }
     }
 
-    pub fn draw_image(&self, image: &HtmlImageElement, orig: &Rect, dest: &Rect) {
-        self
-            .context
+    /// Multiplies the sprite by `tint` on the scratch canvas, then puts the sprite's own
+    /// alpha back, since the multiply also colors its transparent pixels.
+    fn tint(&self, image: &Image, orig: &Rect, tint: [u8; 3]) -> Result<HtmlCanvasElement> {
+        let mut scratch = self.scratch.borrow_mut();
+        let canvas = match scratch.take() {
+            Some(canvas) => canvas,
+            None => browser::new_canvas(orig.width as u32, orig.height as u32)?,
+        };
+        // Resizing also clears it
+        canvas.set_width(orig.width as u32);
+        canvas.set_height(orig.height as u32);
+        let context = browser::context_of(&canvas)?;
+        let local = Rect { x: 0, y: 0, width: orig.width, height: orig.height };
+        let fail = |err| anyhow!("Could not tint a sprite {:#?}", err);
+
+        draw_on(&context, image, orig, &local).map_err(fail)?;
+        context.set_global_composite_operation("multiply").map_err(fail)?;
+        context.set_fill_style_str(&format!("rgb({}, {}, {})", tint[0], tint[1], tint[2]));
+        context.fill_rect(0.0, 0.0, orig.width.into(), orig.height.into());
+        context.set_global_composite_operation("destination-in").map_err(fail)?;
+        draw_on(&context, image, orig, &local).map_err(fail)?;
+
+        *scratch = Some(canvas.clone());
+        Ok(canvas)
+    }
+}
+
+/// Copies `orig` of `image` into `dest` on any canvas context.
+fn draw_on(context: &CanvasRenderingContext2d, image: &Image, orig: &Rect, dest: &Rect) -> Result<(), JsValue> {
+    // Bitmaps only exist on native builds, the browser always hands us elements
+    match image {
+        Image::Element(element) => context
             .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                &image,
+                element,
+
+                orig.x.into(),
+                orig.y.into(),
+                orig.width.into(),
+                orig.height.into(),
+
+                dest.x.into(),
+                dest.y.into(),
+                dest.width.into(),
+                dest.height.into()
+            ),
+        Image::Canvas(canvas) => context
+            .draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                canvas,
 
                 orig.x.into(),
                 orig.y.into(),
@@ -49,18 +328,499 @@ impl Renderer {
                 dest.y.into(),
                 dest.width.into(),
                 dest.height.into()
-            )
+            ),
+        Image::Bitmap(_) => Ok(()),
+    }
+}
+
+impl RenderBackend for CanvasBackend {
+    fn clear(&self, rect: &Rect) {
+        self
+            .context
+            .clear_rect(rect.x.into(), rect.y.into(), rect.width.into(), rect.height.into());
+    }
+
+    fn draw_sub_image(&self, image: &Image, orig: &Rect, dest: &Rect) {
+        draw_on(&self.context, image, orig, dest)
             .expect("Drawing is throwing exceptions! Unrecoverable error.");
     }
+
+    fn save(&self) {
+        self.context.save();
+    }
+
+    fn restore(&self) {
+        self.context.restore();
+    }
+
+    fn translate(&self, x: f64, y: f64) {
+        self
+            .context
+            .translate(x, y)
+            .expect("Could not translate the canvas context");
+    }
+
+    fn rotate(&self, angle: f64) {
+        self
+            .context
+            .rotate(angle)
+            .expect("Could not rotate the canvas context");
+    }
+
+    fn scale(&self, x: f64, y: f64) {
+        self
+            .context
+            .scale(x, y)
+            .expect("Could not scale the canvas context");
+    }
+
+    fn set_global_alpha(&self, alpha: f64) {
+        self.context.set_global_alpha(alpha);
+    }
+
+    fn global_alpha(&self) -> f64 {
+        self.context.global_alpha()
+    }
+
+    fn draw_tinted_sub_image(&self, image: &Image, orig: &Rect, dest: &Rect, tint: [u8; 3]) {
+        // Drawing untinted beats dropping the sprite when the scratch canvas is unavailable
+        match self.tint(image, orig, tint) {
+            Ok(tinted) => self.draw_sub_image(
+                &Image::Canvas(tinted),
+                &Rect { x: 0, y: 0, width: orig.width, height: orig.height },
+                dest
+            ),
+            Err(_) => self.draw_sub_image(image, orig, dest),
+        }
+    }
+
+    fn create_image(&self, bitmap: &Bitmap) -> Result<Image> {
+        let canvas = browser::new_canvas(bitmap.width, bitmap.height)?;
+        let data = ImageData::new_with_u8_clamped_array_and_sh(Clamped(&bitmap.data), bitmap.width, bitmap.height)
+            .map_err(|err| anyhow!("Could not create ImageData {:#?}", err))?;
+
+        browser::context_of(&canvas)?
+            .put_image_data(&data, 0.0, 0.0)
+            .map_err(|err| anyhow!("Could not put image data {:#?}", err))?;
+
+        Ok(Image::Canvas(canvas))
+    }
+}
+
+pub struct Renderer {
+    backend: Box<dyn RenderBackend>,
+}
+
+impl Renderer {
+    pub fn new(backend: impl RenderBackend + 'static) -> Self {
+        Renderer { backend: Box::new(backend) }
+    }
+
+    pub fn clear(&self, rect: &Rect) {
+        self.backend.clear(rect);
+    }
+
+    pub fn draw_image(&self, image: &Image, orig: &Rect, dest: &Rect) {
+        self.backend.draw_sub_image(image, orig, dest);
+    }
+
+    /// Draws `sprite` out of `image` as `draw` describes, leaving the renderer as it was.
+    pub fn draw_sprite(&self, image: &Image, sprite: &Rect, draw: &SpriteDraw) {
+        let (width, height) = self.place(sprite.width, sprite.height, draw);
+        let dest = Rect { x: 0, y: 0, width: width.round() as i32, height: height.round() as i32 };
+        self.draw_placed(image, sprite, &dest, draw);
+        self.restore();
+    }
+
+    /// Draws a sprite of a merged sheet out of its own atlas, `images` follows `Spritesheet::images`.
+    /// Trimmed and rotated sprites are placed as if they were whole and upright.
+    pub fn draw_sheet_sprite(&self, images: &[Image], sprite: &SheetSprite, draw: &SpriteDraw) {
+        let Some(image) = images.get(sprite.atlas) else { return };
+        if sprite.trim.is_none() && !sprite.rotated {
+            return self.draw_sprite(image, &sprite.rect, draw);
+        }
+
+        self.place(sprite.width(), sprite.height(), draw);
+        if let Some(trim) = sprite.trim.as_ref() {
+            self.translate(trim.x as f64 * draw.scale.x, trim.y as f64 * draw.scale.y);
+        }
+
+        let width = sprite.rect.width as f64;
+        let height = sprite.rect.height as f64;
+        let dest = if sprite.rotated {
+            // The packed pixels lie on their side, a quarter turn back about their
+            // bottom-left corner stands them up
+            self.translate(0.0, width * draw.scale.y);
+            self.rotate(-FRAC_PI_2);
+            Rect { x: 0, y: 0, width: (width * draw.scale.y).round() as i32, height: (height * draw.scale.x).round() as i32 }
+        } else {
+            Rect { x: 0, y: 0, width: (width * draw.scale.x).round() as i32, height: (height * draw.scale.y).round() as i32 }
+        };
+        self.draw_placed(image, &sprite.rect, &dest, draw);
+        self.restore();
+    }
+
+    /// Saves the renderer and moves it so a sprite of `width` by `height` px lands at
+    /// the origin as `draw` describes. Returns the scaled size.
+    fn place(&self, width: i32, height: i32, draw: &SpriteDraw) -> (f64, f64) {
+        let width = width as f64 * draw.scale.x;
+        let height = height as f64 * draw.scale.y;
+        let pivot_x = draw.origin.x * width;
+        let pivot_y = draw.origin.y * height;
+        let mirror = |flipped: bool| if flipped { -1.0 } else { 1.0 };
+
+        self.save();
+        if draw.alpha != 1.0 {
+            self.set_global_alpha(self.backend.global_alpha() * draw.alpha.clamp(0.0, 1.0));
+        }
+
+        // Snapping the corner to whole pixels keeps unturned sprites as crisp as a plain copy
+        self.translate(
+            (draw.position.x - pivot_x).floor() + pivot_x,
+            (draw.position.y - pivot_y).floor() + pivot_y
+        );
+        if draw.rotation != 0.0 {
+            self.rotate(draw.rotation);
+        }
+        if draw.flip_x || draw.flip_y {
+            self.scale(mirror(draw.flip_x), mirror(draw.flip_y));
+        }
+        self.translate(-pivot_x, -pivot_y);
+
+        (width, height)
+    }
+
+    fn draw_placed(&self, image: &Image, sprite: &Rect, dest: &Rect, draw: &SpriteDraw) {
+        match draw.tint {
+            Some(tint) => self.backend.draw_tinted_sub_image(image, sprite, dest, tint),
+            None => self.backend.draw_sub_image(image, sprite, dest),
+        }
+    }
+
+    pub fn save(&self) {
+        self.backend.save();
+    }
+
+    pub fn restore(&self) {
+        self.backend.restore();
+    }
+
+    pub fn translate(&self, x: f64, y: f64) {
+        self.backend.translate(x, y);
+    }
+
+    pub fn rotate(&self, angle: f64) {
+        self.backend.rotate(angle);
+    }
+
+    pub fn scale(&self, x: f64, y: f64) {
+        self.backend.scale(x, y);
+    }
+
+    pub fn set_global_alpha(&self, alpha: f64) {
+        self.backend.set_global_alpha(alpha);
+    }
+
+    pub fn create_image(&self, bitmap: &Bitmap) -> Result<Image> {
+        self.backend.create_image(bitmap)
+    }
+}
+
+/// Where and how `Renderer::draw_sprite` puts a sprite.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteDraw {
+    /// Where `origin` lands on the canvas.
+    pub position: Position,
+    /// Point the sprite is placed, turned and flipped about, as a fraction of its size:
+    /// `(0, 0)` is the top-left corner and `(0.5, 0.5)` the middle.
+    pub origin: Position,
+    /// Radians, positive turns clockwise.
+    pub rotation: f64,
+    /// Horizontal and vertical stretch of the sprite's own size.
+    pub scale: Position,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Opacity, on top of whatever the renderer already applies.
+    pub alpha: f64,
+    /// RGB every pixel is multiplied by, so white leaves the sprite as it is.
+    pub tint: Option<[u8; 3]>,
+}
+
+impl Default for SpriteDraw {
+    fn default() -> Self {
+        SpriteDraw {
+            position: Position::default(),
+            origin: Position::default(),
+            rotation: 0.0,
+            scale: Position { x: 1.0, y: 1.0 },
+            flip_x: false,
+            flip_y: false,
+            alpha: 1.0,
+            tint: None,
+        }
+    }
+}
+
+impl SpriteDraw {
+    /// The sprite as it is, top-left corner at `x`, `y`.
+    pub fn at(x: f64, y: f64) -> Self {
+        SpriteDraw { position: Position { x, y }, ..SpriteDraw::default() }
+    }
+
+    /// The sprite as it is, its middle at `x`, `y`.
+    pub fn centered(x: f64, y: f64) -> Self {
+        SpriteDraw { origin: Position { x: 0.5, y: 0.5 }, ..SpriteDraw::at(x, y) }
+    }
+
+    /// The sprite as it is, its pivot at `x`, `y`, or its middle when it has none.
+    pub fn pivoted(sprite: &SheetSprite, x: f64, y: f64) -> Self {
+        SpriteDraw { origin: sprite.pivot.unwrap_or(Position { x: 0.5, y: 0.5 }), ..SpriteDraw::at(x, y) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// How a font lays a string out around its position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// Multiplies the font's own size.
+    pub scale: f64,
+    /// Which part of every line sits on the position's x.
+    pub align: Align,
+    /// Width in px past which lines wrap between words.
+    pub max_width: Option<f64>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle { scale: 1.0, align: Align::Left, max_width: None }
+    }
+}
+
+/// Text drawn from atlas sprites, one per character.
+#[derive(Debug, Clone)]
+pub struct SpriteFont {
+    glyphs: HashMap<char, SheetSprite>,
+    /// Extra px between every pair of glyphs, before scaling.
+    spacing: i32,
+    /// Extra px between specific pairs of glyphs, before scaling.
+    kerning: HashMap<(char, char), i32>,
+    space_width: i32,
+    line_height: i32,
+}
+
+impl SpriteFont {
+    /// Builds a font from the sprites `name_of` gives for each character, skipping the
+    /// ones the sheet does not have.
+    pub fn from_sheet(sheet: &Spritesheet, characters: impl IntoIterator<Item = char>, name_of: impl Fn(char) -> String) -> Result<Self> {
+        let glyphs: HashMap<char, SheetSprite> = characters
+            .into_iter()
+            .filter_map(|character| sheet.tileset.get(&name_of(character)).map(|sprite| (character, sprite.clone())))
+            .collect();
+        if glyphs.is_empty() {
+            return Err(anyhow!("The sheet has none of the font's glyphs"));
+        }
+
+        // Spaces are as wide as the narrowest glyph
+        let space_width = glyphs.values().map(SheetSprite::width).min().unwrap_or(0);
+        let line_height = glyphs.values().map(SheetSprite::height).max().unwrap_or(0);
+
+        Ok(SpriteFont { glyphs, spacing: 0, kerning: HashMap::new(), space_width, line_height })
+    }
+
+    pub fn with_spacing(mut self, spacing: i32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    pub fn with_kerning(mut self, pairs: impl IntoIterator<Item = ((char, char), i32)>) -> Self {
+        self.kerning.extend(pairs);
+        self
+    }
+
+    pub fn line_height(&self, scale: f64) -> f64 {
+        self.line_height as f64 * scale
+    }
+
+    fn glyph(&self, character: char) -> Option<&SheetSprite> {
+        self.glyphs
+            .get(&character)
+            .or_else(|| self.glyphs.get(&character.to_ascii_uppercase()))
+    }
+
+    /// Draws `text` with its first line's top edge at `position.y`. Glyphs of different
+    /// heights share their bottom edge.
+    pub fn draw(&self, text: &str, position: &Position, style: &TextStyle, renderer: &Renderer, images: &[Image]) {
+        let line_height = self.line_height(style.scale);
+
+        for (index, line) in self.layout(text, position.x, style).iter().enumerate() {
+            let bottom = position.y + line_height * (index + 1) as f64;
+
+            for &(character, x) in line {
+                let Some(glyph) = self.glyph(character) else { continue };
+                let height = glyph.height() as f64 * style.scale;
+
+                renderer.draw_sheet_sprite(
+                    images,
+                    glyph,
+                    &SpriteDraw {
+                        scale: Position { x: style.scale, y: style.scale },
+                        ..SpriteDraw::at(x, bottom - height)
+                    }
+                );
+            }
+        }
+    }
+}
+
+impl TextLayout for SpriteFont {
+    fn advances(&self, line: &str, scale: f64) -> (Vec<f64>, f64) {
+        let mut offsets = Vec::new();
+        let mut x = 0.0;
+        let mut previous: Option<char> = None;
+
+        for character in line.chars() {
+            if let Some(previous) = previous {
+                let kerning = self.kerning.get(&(previous.to_ascii_uppercase(), character.to_ascii_uppercase()));
+                x += (self.spacing + kerning.copied().unwrap_or(0)) as f64 * scale;
+            }
+            offsets.push(x);
+            x += self.glyph(character).map_or(self.space_width, SheetSprite::width) as f64 * scale;
+            previous = Some(character);
+        }
+
+        (offsets, x)
+    }
+}
+
+/// Wrapping, measuring and aligning lines, the same for every font. A font only says
+/// where the characters of a single line go.
+pub trait TextLayout {
+    /// Offset of every character of `line` from its start, plus the line's width.
+    fn advances(&self, line: &str, scale: f64) -> (Vec<f64>, f64);
+
+    fn lines(&self, text: &str, style: &TextStyle) -> Vec<String> {
+        wrap_lines(text, style.max_width, |line| self.advances(line, style.scale).1)
+    }
+
+    /// Width of the widest line once laid out with `style`.
+    fn measure(&self, text: &str, style: &TextStyle) -> f64 {
+        self.lines(text, style)
+            .iter()
+            .map(|line| self.advances(line, style.scale).1)
+            .fold(0.0, f64::max)
+    }
+
+    /// Every line of `text`, as its characters and the x each one starts at once the
+    /// line is aligned around `x`.
+    fn layout(&self, text: &str, x: f64, style: &TextStyle) -> Vec<Vec<(char, f64)>> {
+        self.lines(text, style)
+            .iter()
+            .map(|line| {
+                let (offsets, width) = self.advances(line, style.scale);
+                let start = align_start(x, width, style.align);
+                line.chars().zip(offsets).map(|(character, offset)| (character, start + offset)).collect()
+            })
+            .collect()
+    }
+}
+
+/// Splits `text` on new lines, then between words wherever a line measured with
+/// `width_of` would overflow `max_width`. A word wider than that gets a line of its own.
+fn wrap_lines(text: &str, max_width: Option<f64>, width_of: impl Fn(&str) -> f64) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let Some(max_width) = max_width else {
+            lines.push(paragraph.to_string());
+            continue;
+        };
+
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if !line.is_empty() && width_of(&candidate) > max_width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Left edge of a line `width` px wide laid out around `x`.
+fn align_start(x: f64, width: f64, align: Align) -> f64 {
+    match align {
+        Align::Left => x,
+        Align::Center => x - width / 2.0,
+        Align::Right => x - width,
+    }
 }
 
 #[async_trait(?Send)]
 pub trait Game {
     async fn init(&self) -> Result<Box<dyn Game>>;
-    fn update(&mut self, delta: &f64, input: &bool);
-    fn draw(&self, renderer: &Renderer);
+    fn seed(&self) -> u64;
+    /// What the loaded game was built from, for the replay being recorded.
+    fn session_start(&self) -> SessionStart;
+    /// Starts over from `start`, keeping the seed and whatever was loaded to draw with.
+    fn restore(&mut self, start: &SessionStart) -> Result<()>;
+    fn score(&self) -> Option<u32>;
+    fn update(&mut self, delta: &f64, input: &InputState);
+    fn draw(&self, renderer: &Renderer, alpha: &f64);
+}
+
+/// Accumulates wall-clock time and hands it back as a whole number of fixed ticks.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    tick_rate: f64,
+    max_steps: u32,
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    pub fn new(tick_rate: f64, max_steps: u32) -> Self {
+        FixedTimestep { tick_rate, max_steps, accumulator: 0.0 }
+    }
+
+    pub fn delta(&self) -> f64 {
+        1.0 / self.tick_rate
+    }
+
+    /// Returns how many ticks to simulate for `frame_delta` seconds. Anything past
+    /// `max_steps` ticks is dropped so a long stall cannot snowball.
+    pub fn advance(&mut self, frame_delta: f64) -> u32 {
+        let delta = self.delta();
+        self.accumulator += frame_delta.max(0.0);
+
+        let mut steps = 0;
+        while self.accumulator >= delta && steps < self.max_steps {
+            self.accumulator -= delta;
+            steps += 1;
+        }
+
+        if steps == self.max_steps {
+            self.accumulator = self.accumulator.min(delta);
+        }
+
+        steps
+    }
+
+    /// How far the current frame sits between the last tick and the next one.
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.delta()).clamp(0.0, 1.0)
+    }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub struct Position {
     pub x: f64,
     pub y: f64,
@@ -68,64 +828,133 @@ pub struct Position {
 
 pub struct Engine;
 
+type SharedLoopClosure = Rc<RefCell<Option<Closure<dyn FnMut(f64)>>>>;
+
 impl Engine {
-    pub async fn start<T: Game + 'static>(game: T) -> Result<()> {
+    pub async fn start<T: Game + 'static>(
+        game: T,
+        mut timestep: FixedTimestep,
+        recording: Rc<RefCell<Replay>>,
+        devices: Rc<RefCell<InputDevices>>,
+    ) -> Result<()> {
         let mut game = game.init().await?;
+        recording.borrow_mut().begin(game.session_start());
 
-        let renderer = Renderer {
-            context: browser::context()?,
-        };
+        let renderer = Renderer::new(CanvasBackend::new(browser::context()?));
 
-        let input= Rc::new(Cell::new(false));
+        let release = |devices: &Rc<RefCell<InputDevices>>| devices.borrow_mut().release(&Binding::Pointer);
+        // Releases are not heard once the page loses focus, so everything is let go
+        let lose_focus = |devices: &Rc<RefCell<InputDevices>>| {
+            let mut devices = devices.borrow_mut();
+            devices.release_all();
+            devices.request(Action::Suspend);
+        };
         {
-            let pressed = input.clone();
-            
-             let listener = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| pressed.set(true) );
+            let pressed = devices.clone();
+            let canvas = browser::canvas()?;
+            let listener = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| {
+                let pointer = browser::canvas_position(&canvas, event.client_x() as f64, event.client_y() as f64);
+                let mut devices = pressed.borrow_mut();
+                devices.move_pointer(pointer);
+                devices.press(Binding::Pointer);
+            });
             browser::canvas()?.add_event_listener_with_callback("mousedown", listener.as_ref().unchecked_ref()).expect("Could not add mousedown listener to canvas");
             listener.forget();
 
-            let pressed = input.clone();
-            let listener = Closure::<dyn FnMut(_)>::new(move |event: TouchEvent| pressed.set(true) );
+            let pressed = devices.clone();
+            let canvas = browser::canvas()?;
+            let listener = Closure::<dyn FnMut(_)>::new(move |event: TouchEvent| {
+                let mut devices = pressed.borrow_mut();
+                if let Some(touch) = event.changed_touches().get(0) {
+                    devices.move_pointer(browser::canvas_position(&canvas, touch.client_x() as f64, touch.client_y() as f64));
+                }
+                devices.press(Binding::Pointer);
+            });
             browser::canvas()?.add_event_listener_with_callback("touchstart", listener.as_ref().unchecked_ref()).expect("Could not add touchstart listener to canvas");
             listener.forget();
 
-            let pressed = input.clone();
-            let listener = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| pressed.set(false) );
+            let pressed = devices.clone();
+            let listener = Closure::<dyn FnMut(_)>::new(move |_event: MouseEvent| release(&pressed) );
             browser::canvas()?.add_event_listener_with_callback("mouseup", listener.as_ref().unchecked_ref()).expect("Could not add mouseup listener to canvas");
             listener.forget();
             
-            let pressed = input.clone();
-            let listener = Closure::<dyn FnMut(_)>::new(move |event: TouchEvent| pressed.set(false) );
+            let pressed = devices.clone();
+            let listener = Closure::<dyn FnMut(_)>::new(move |_event: TouchEvent| release(&pressed) );
             browser::canvas()?.add_event_listener_with_callback("touchend", listener.as_ref().unchecked_ref()).expect("Could not add touchend listener to canvas");
             listener.forget();
 
 
-            let pressed = input.clone();
-            let listener = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| pressed.set(false) );
+            let pressed = devices.clone();
+            let listener = Closure::<dyn FnMut(_)>::new(move |_event: MouseEvent| release(&pressed) );
             browser::canvas()?.add_event_listener_with_callback("mouseleave", listener.as_ref().unchecked_ref()).expect("Could not add mouseleave listener to canvas");
             listener.forget();
 
-            let pressed = input.clone();
-            let listener = Closure::<dyn FnMut(_)>::new(move |event: TouchEvent| pressed.set(false) );
+            let pressed = devices.clone();
+            let listener = Closure::<dyn FnMut(_)>::new(move |_event: TouchEvent| release(&pressed) );
             browser::canvas()?.add_event_listener_with_callback("touchcancel", listener.as_ref().unchecked_ref()).expect("Could not add touchcancel listener to canvas");
             listener.forget();
+
+            let pressed = devices.clone();
+            let listener = Closure::<dyn FnMut(_)>::new(move |event: KeyboardEvent| {
+                let key = Binding::Key(event.code());
+                let mut devices = pressed.borrow_mut();
+                if devices.map().is_bound(&key) {
+                    // Keep Space and the arrows from scrolling the page
+                    event.prevent_default();
+                    if !event.repeat() {
+                        devices.press(key);
+                    }
+                }
+            });
+            browser::window()?.add_event_listener_with_callback("keydown", listener.as_ref().unchecked_ref()).expect("Could not add keydown listener to window");
+            listener.forget();
+
+            let pressed = devices.clone();
+            let listener = Closure::<dyn FnMut(_)>::new(move |event: KeyboardEvent| {
+                pressed.borrow_mut().release(&Binding::Key(event.code()));
+            });
+            browser::window()?.add_event_listener_with_callback("keyup", listener.as_ref().unchecked_ref()).expect("Could not add keyup listener to window");
+            listener.forget();
+
+            // Hidden tabs stop getting animation frames, pause before the clock jumps
+            let paused = devices.clone();
+            let listener = Closure::<dyn FnMut(_)>::new(move |_event: Event| {
+                if browser::document().is_ok_and(|document| document.hidden()) {
+                    lose_focus(&paused);
+                }
+            });
+            browser::document()?.add_event_listener_with_callback("visibilitychange", listener.as_ref().unchecked_ref()).expect("Could not add visibilitychange listener to document");
+            listener.forget();
+
+            let paused = devices.clone();
+            let listener = Closure::<dyn FnMut(_)>::new(move |_event: Event| lose_focus(&paused) );
+            browser::window()?.add_event_listener_with_callback("blur", listener.as_ref().unchecked_ref()).expect("Could not add blur listener to window");
+            listener.forget();
         }
 
-        let f: Rc<RefCell<Option<Closure<dyn FnMut(f64)>>>> = Rc::new(RefCell::new(None));
+        let f: SharedLoopClosure = Rc::new(RefCell::new(None));
         let g = f.clone();
 
         let mut previous_time: f64 = browser::now()?; 
 
-        let animate = Some(browser::create_animation_closure(move |js_delta: f64| {
+        let animate = Some(browser::create_animation_closure(move |_js_delta: f64| {
             let current_time: f64 = browser::now().unwrap();
-            let delta = (current_time - previous_time) / 1000.0;
+            let frame_delta = (current_time - previous_time) / 1000.0;
 
-            game.update(&delta, &input.get());
-            game.draw(&renderer);
+            devices.borrow_mut().set_gamepad_buttons(browser::gamepad_buttons());
+
+            let delta = timestep.delta();
+            for _ in 0..timestep.advance(frame_delta) {
+                let input = devices.borrow_mut().poll();
+                recording.borrow_mut().record(delta, input);
+                game.update(&delta, &input);
+            }
+            game.draw(&renderer, &timestep.alpha());
 
             previous_time = current_time;
 
-            browser::request_animation_frame(f.borrow().as_ref().unwrap());
+            browser::request_animation_frame(f.borrow().as_ref().unwrap())
+                .expect("Could not request the next animation frame");
         }));
 
         *g.borrow_mut() = animate;
@@ -144,13 +973,13 @@ pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
 
         let callback = Closure::once(Box::new(move || {
             if let Some(success_tx) = success_tx.lock().ok().and_then(|mut opt| opt.take()) {
-                success_tx.send(Ok(()));
+                let _ = success_tx.send(Ok(()));
             }
         }));
 
         let error_callback = Closure::once(Box::new(move |err| {
             if let Some(error_tx) = error_tx.lock().ok().and_then(|mut opt| opt.take()) {
-                error_tx.send(Err(err));
+                let _ = error_tx.send(Err(err));
             }
         }));
                                  
@@ -158,6 +987,95 @@ pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
         image.set_onload(Some(callback.as_ref().unchecked_ref()));
         image.set_src(source);
 
-        success_rx.await;
+        success_rx
+            .await
+            .map_err(|err| anyhow!("Image loading was cancelled {:#?}", err))?
+            .map_err(|err| anyhow!("Could not load image {}: {:#?}", source, err))?;
     Ok(image)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    #[test]
+    fn sheets_list_their_sprites_as_a_map_or_an_array() {
+        let map = Spritesheet::from_json(r#"{ "image": "a.png", "tileset": { "rock": { "x": 1, "y": 2, "width": 3, "height": 4 } } }"#).unwrap();
+        let list = Spritesheet::from_json(r#"{ "image": "a.png", "tileset": [{ "name": "rock", "rect": { "x": 1, "y": 2, "width": 3, "height": 4 } }] }"#).unwrap();
+
+        assert_eq!(list.images, ["a.png"]);
+        assert_eq!(list.tileset, map.tileset);
+        assert_eq!(list.tileset["rock"], SheetSprite { rect: rect(1, 2, 3, 4), ..SheetSprite::default() });
+    }
+
+    #[test]
+    fn sprites_listed_twice_in_an_array_sheet_are_refused() {
+        let err = Spritesheet::from_json(r#"{ "image": "a.png", "tileset": [
+            { "name": "rock", "rect": { "x": 0, "y": 0, "width": 1, "height": 1 } },
+            { "name": "rock", "rect": { "x": 1, "y": 0, "width": 1, "height": 1 } }
+        ] }"#).unwrap_err();
+
+        assert!(err.to_string().contains("rock is listed twice"));
+    }
+
+    #[test]
+    fn merged_sprites_point_at_their_own_atlas() {
+        let first = Spritesheet::from_json(r#"{ "image": "a.png", "tileset": { "rock": { "x": 0, "y": 0, "width": 1, "height": 1 } } }"#).unwrap();
+        let second = Spritesheet::from_json(r#"{ "image": "b.png", "tileset": [{ "name": "star", "rect": { "x": 5, "y": 6, "width": 7, "height": 8 } }] }"#).unwrap();
+
+        let merged = first.merge(second).unwrap();
+
+        assert_eq!(merged.images, ["a.png", "b.png"]);
+        assert_eq!(merged.tileset["rock"].atlas, 0);
+        assert_eq!(merged.tileset["star"], SheetSprite { atlas: 1, rect: rect(5, 6, 7, 8), ..SheetSprite::default() });
+    }
+
+    #[test]
+    fn merging_sheets_that_share_a_name_is_an_error() {
+        let first = Spritesheet::from_json(r#"{ "image": "a.png", "tileset": { "rock": { "x": 0, "y": 0, "width": 1, "height": 1 } } }"#).unwrap();
+        let second = Spritesheet::from_json(r#"{ "image": "b.png", "tileset": [{ "name": "rock", "rect": { "x": 0, "y": 0, "width": 1, "height": 1 } }] }"#).unwrap();
+
+        let err = first.merge(second).unwrap_err();
+
+        assert!(err.to_string().contains("named rock"));
+    }
+
+    #[test]
+    fn a_frame_runs_the_ticks_it_covers_and_keeps_the_rest() {
+        let mut timestep = FixedTimestep::new(4.0, 5);
+
+        assert_eq!(timestep.advance(0.625), 2);
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(0.125), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn catching_up_stops_at_max_steps() {
+        let mut timestep = FixedTimestep::new(60.0, 5);
+
+        assert_eq!(timestep.advance(3.0), 5);
+    }
+
+    #[test]
+    fn a_long_frame_leaves_at_most_one_tick_behind() {
+        let mut timestep = FixedTimestep::new(60.0, 5);
+        timestep.advance(3.0);
+
+        assert_eq!(timestep.alpha(), 1.0);
+        assert_eq!(timestep.advance(0.0), 1);
+        assert_eq!(timestep.advance(0.0), 0);
+    }
+
+    #[test]
+    fn time_going_backwards_runs_nothing() {
+        let mut timestep = FixedTimestep::new(60.0, 5);
+
+        assert_eq!(timestep.advance(-1.0), 0);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+}
@@ -0,0 +1,145 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use anyhow::{anyhow, Result};
+use fontdue::{Font, FontSettings};
+
+use crate::{
+    engine::{Image, Position, Rect, Renderer, TextLayout, TextStyle},
+    software::Bitmap,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    character: char,
+    size: u32,
+    color: [u8; 4],
+}
+
+struct CachedGlyph {
+    image: Image,
+    width: i32,
+    height: i32,
+}
+
+/// Outline font rasterized on demand. Every character is rasterized once per size and
+/// color, then drawn from the cache like any other image, so the same layout works on
+/// the canvas and the software backend.
+pub struct TrueTypeFont {
+    font: Font,
+    /// Em size in px at a scale of 1.
+    size: f64,
+    /// RGBA, the alpha scales the glyphs' own coverage.
+    color: [u8; 4],
+    /// `None` for glyphs with nothing to draw, like spaces.
+    cache: RefCell<HashMap<GlyphKey, Option<CachedGlyph>>>,
+}
+
+impl TrueTypeFont {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let font = Font::from_bytes(bytes, FontSettings::default())
+            .map_err(|err| anyhow!("Could not parse font: {}", err))?;
+
+        Ok(TrueTypeFont { font, size: 16.0, color: [255, 255, 255, 255], cache: RefCell::new(HashMap::new()) })
+    }
+
+    pub fn with_size(mut self, size: f64) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_color(mut self, color: [u8; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Em size at `scale`, rounded to whole pixels so the cache stays small.
+    fn px(&self, scale: f64) -> f32 {
+        (self.size * scale).round().max(1.0) as f32
+    }
+
+    /// Distance from the top of a line to its baseline, and from one baseline to the next.
+    fn line_metrics(&self, scale: f64) -> (f64, f64) {
+        let px = self.px(scale);
+        match self.font.horizontal_line_metrics(px) {
+            Some(metrics) => (metrics.ascent as f64, metrics.new_line_size as f64),
+            None => (px as f64, px as f64 * 1.2),
+        }
+    }
+
+    pub fn line_height(&self, scale: f64) -> f64 {
+        self.line_metrics(scale).1
+    }
+
+    fn rasterize(&self, key: &GlyphKey, renderer: &Renderer) -> Result<Option<CachedGlyph>> {
+        let (metrics, coverage) = self.font.rasterize(key.character, key.size as f32);
+        if metrics.width == 0 || metrics.height == 0 {
+            return Ok(None);
+        }
+
+        let [red, green, blue, alpha] = key.color;
+        let data = coverage
+            .iter()
+            .flat_map(|&covered| [red, green, blue, (covered as u32 * alpha as u32 / 255) as u8])
+            .collect();
+        let bitmap = Bitmap::from_rgba(metrics.width as u32, metrics.height as u32, data)?;
+
+        Ok(Some(CachedGlyph {
+            image: renderer.create_image(&bitmap)?,
+            width: metrics.width as i32,
+            height: metrics.height as i32,
+        }))
+    }
+
+    /// Draws `text` with its first line's top edge at `position.y`. A glyph the backend
+    /// cannot make an image of is left out rather than failing the whole frame.
+    pub fn draw(&self, text: &str, position: &Position, style: &TextStyle, renderer: &Renderer) {
+        let px = self.px(style.scale);
+        let (ascent, line_height) = self.line_metrics(style.scale);
+        let mut cache = self.cache.borrow_mut();
+
+        for (index, line) in self.layout(text, position.x, style).iter().enumerate() {
+            let baseline = position.y + ascent + line_height * index as f64;
+
+            for &(character, x) in line {
+                let key = GlyphKey { character, size: px as u32, color: self.color };
+                let glyph = cache
+                    .entry(key)
+                    .or_insert_with(|| self.rasterize(&key, renderer).ok().flatten());
+                let Some(glyph) = glyph else { continue };
+
+                // Placement comes from the metrics, the cached bitmap only knows its size
+                let metrics = self.font.metrics(character, px);
+                renderer.draw_image(
+                    &glyph.image,
+                    &Rect { x: 0, y: 0, width: glyph.width, height: glyph.height },
+                    &Rect {
+                        x: (x + metrics.xmin as f64).round() as i32,
+                        y: (baseline - metrics.ymin as f64 - glyph.height as f64).round() as i32,
+                        width: glyph.width,
+                        height: glyph.height,
+                    }
+                );
+            }
+        }
+    }
+}
+
+impl TextLayout for TrueTypeFont {
+    fn advances(&self, line: &str, scale: f64) -> (Vec<f64>, f64) {
+        let px = self.px(scale);
+        let mut offsets = Vec::new();
+        let mut x = 0.0;
+        let mut previous: Option<char> = None;
+
+        for character in line.chars() {
+            if let Some(previous) = previous {
+                x += self.font.horizontal_kern(previous, character, px).unwrap_or(0.0) as f64;
+            }
+            offsets.push(x);
+            x += self.font.metrics(character, px).advance_width as f64;
+            previous = Some(character);
+        }
+
+        (offsets, x)
+    }
+}
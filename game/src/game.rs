@@ -1,296 +1,839 @@
-use crate::{engine::{Game, Renderer, Spritesheet, Rect, self, Position}, browser, physics::World};
+use crate::{
+    atlas,
+    biome::BiomeBlend,
+    config::{GameConfig, Medal, Skin},
+    difficulty::{Difficulty, OffsetRange, PLANE_SIZE, PLANE_X, ROCK_SIZE},
+    engine::{Game, Renderer, Spritesheet, SpriteDraw, SpriteFont, Rect, self, Position, Image, TextStyle, Align}, 
+    sprites::{self, Outlines, Sprite, Sprites}, 
+    browser::{self, LocalStorageStore}, 
+    font::TrueTypeFont,
+    input::{Action, InputState},
+    outline::ColliderOutlines,
+    particles::{Curve, Emitter, ParticleSettings, Spread},
+    physics::World, 
+    replay::SessionStart,
+    scores::{HighScores, MemoryStore, ScoreEntry}, 
+    utils::{self, Dimension, Rng},
+};
 
-use anyhow::Result;
+use std::f64::consts::PI;
+
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use rapier2d::{geometry::ColliderHandle, dynamics::RigidBodyHandle};
-use serde::de::value;
-use web_sys::HtmlImageElement;
+use rapier2d::dynamics::RigidBodyHandle;
+
+/// Width of the ground sprites, the terrain wraps around after scrolling this far.
+const TERRAIN_WIDTH: f64 = 808.0;
+const SCORES_KEY: &str = "tappy-plane-scores";
+const HIGH_SCORE_CAPACITY: usize = 10;
+const PANEL_ANIMATION_TIME: f64 = 0.6;
+const STAR_SIZE: Dimension = Dimension { width: 39, height: 37 };
+const PARTICLE_CAPACITY: usize = 96;
+const EXHAUST_INTERVAL: f64 = 0.08;
+/// Seconds of 3, 2, 1 before a paused run picks up again.
+const RESUME_COUNTDOWN: f64 = 3.0;
+/// Atlases merged into one spritesheet, so sprite and clip names must not repeat across
+/// them. Each is a plain sheet or a TexturePacker or Aseprite export.
+const SHEETS: [&str; 2] = ["/assets/sheet.json", "/assets/Spritesheet/planes.json"];
+const LABEL_FONT: &str = "/assets/Font/kenvector_future_thin.ttf";
+const LABEL_SIZE: f64 = 22.0;
+const LABEL_COLOR: [u8; 4] = [74, 110, 140, 255];
+
+const EXHAUST: ParticleSettings = ParticleSettings {
+    sprite: Sprite::PuffSmall,
+    lifetime: Spread { min: 0.4, max: 0.7 },
+    speed: Spread { min: 10.0, max: 30.0 },
+    direction: Spread { min: PI * 0.9, max: PI * 1.1 },
+    scale: Curve { start: 0.4, end: 0.9 },
+    alpha: Curve { start: 0.7, end: 0.0 },
+    gravity: -20.0,
+};
+/// Air pushed down by a flap.
+const TAP_BURST: ParticleSettings = ParticleSettings {
+    sprite: Sprite::PuffSmall,
+    lifetime: Spread { min: 0.25, max: 0.45 },
+    speed: Spread { min: 60.0, max: 120.0 },
+    direction: Spread { min: PI * 0.25, max: PI * 0.75 },
+    scale: Curve { start: 0.6, end: 0.2 },
+    alpha: Curve { start: 0.9, end: 0.0 },
+    gravity: 0.0,
+};
+const CRASH: ParticleSettings = ParticleSettings {
+    sprite: Sprite::PuffLarge,
+    lifetime: Spread { min: 0.6, max: 1.0 },
+    speed: Spread { min: 40.0, max: 160.0 },
+    direction: Spread { min: 0.0, max: PI * 2.0 },
+    scale: Curve { start: 0.5, end: 1.3 },
+    alpha: Curve { start: 1.0, end: 0.0 },
+    gravity: -30.0,
+};
+
+/// Everything that outlives a single state.
+pub struct Session {
+    pub seed: u64,
+    /// Hands every run its own seed, so retries fly new courses and a replay of the
+    /// session still flies the same ones.
+    run_seeds: Rng,
+    /// Index of the flown plane in `GameConfig::skins`.
+    pub skin: usize,
+    pub config: GameConfig,
+    pub scores: HighScores,
+    /// What `outlines` was resolved from, kept for replays.
+    colliders: ColliderOutlines,
+    pub outlines: Outlines,
+}
+
+impl Session {
+    pub fn new(seed: u64, config: GameConfig, scores: HighScores, colliders: ColliderOutlines) -> Self {
+        Session { 
+            seed, 
+            run_seeds: Rng::new(seed),
+            skin: config.skins.iter().position(|skin| skin.unlock_score == 0).unwrap_or(0), 
+            outlines: Outlines::resolve(&colliders, &config),
+            config,
+            scores, 
+            colliders,
+        }
+    }
+
+    pub fn skin(&self) -> &Skin {
+        &self.config.skins[self.skin]
+    }
 
-const CANVAS_WIDTH: f64 = 800.0;
-const CANVAS_HEIGHT: f64 = 480.0;
+    pub fn next_run_seed(&mut self) -> u64 {
+        self.run_seeds.next_u64()
+    }
+}
 
 pub trait GameState {
-    fn update(&mut self, delta: &f64, input: &bool) -> Option<Box<dyn GameState>>;
-    fn draw(&self, renderer: &Renderer, image: &HtmlImageElement, sheet: &Spritesheet);
+    fn update(&mut self, session: &mut Session, delta: &f64, input: &InputState) -> Option<Box<dyn GameState>>;
+    fn draw(&self, session: &Session, renderer: &Renderer, images: &[Image], sprites: &Sprites, fonts: &Fonts, alpha: &f64);
+
+    fn score(&self) -> Option<u32> {
+        None
+    }
+
+    /// Whether a pause request freezes this state behind `Paused`.
+    fn pausable(&self) -> bool {
+        false
+    }
 }
 
 pub struct Waiting;
 
+/// Plane selection: tapping either side of the screen or left and right cycle through
+/// the skins, tapping the middle or confirming flies the selected one if it is unlocked.
+pub struct Hangar {
+    selected: usize,
+}
+
 pub struct GetReady {
-    scroll_speed: f64,
     time_elapsed: f64,
 }
 
+/// Freezes a run until the player taps, then counts 3, 2, 1 before handing it back.
+pub struct Paused {
+    /// Only `None` for the tick that hands the run back.
+    run: Option<Box<dyn GameState>>,
+    /// Seconds since the player asked to resume.
+    resuming: Option<f64>,
+}
+
 pub struct Playing {
-    plane_frame: f64,
-    scroll_speed: f64,
+    /// Seed of this run's course, recorded with its score.
+    seed: u64,
+    rng: Rng,
+    /// Seconds into the propeller animation.
+    plane_time: f64,
+    /// Distance scrolled so far, which drives the difficulty curve.
+    distance: f64,
+    difficulty: Difficulty,
     terrain_offset: f64,
-    obstacles: Vec<Position>,
-    distance_between_obstacles: f64,
+    last_scroll: f64,
+    obstacles: Vec<Obstacle>,
+    score: u32,
+    stars: Vec<Star>,
+    /// Value of the stars picked up so far, kept apart from the score.
+    star_total: u32,
+    particles: Emitter,
+    exhaust_timer: f64,
+    /// Seconds left before another press can flap.
+    flap_cooldown: f64,
+    /// Seconds the current press may keep lifting the plane.
+    flap_hold: f64,
 
     world: World,
-    plane_collider: Option<RigidBodyHandle>
+    plane_collider: Option<RigidBodyHandle>,
+    previous_plane_position: Option<Position>,
+    /// Clockwise pitch of the plane in radians, see `Tilt`.
+    plane_angle: f64,
+    previous_plane_angle: f64,
+}
+#[derive(Clone)]
+pub struct Obstacle {
+    body: RigidBodyHandle,
+    /// Top-left corner, copied from the physics body after every step.
+    position: Position,
+    passed: bool,
+    /// Rocks keep the look of the biome they were spawned in, by its index in `BiomeCycle::biomes`.
+    biome: usize,
+    pointing_up: bool,
+}
+
+#[derive(Clone)]
+pub struct Star {
+    body: RigidBodyHandle,
+    /// Top-left corner, copied from the physics body after every step.
+    position: Position,
+    kind: StarKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StarKind {
+    Bronze,
+    Silver,
+    Gold,
+}
+
+impl StarKind {
+    /// Rarer stars are worth more.
+    fn pick(rng: &mut Rng) -> Self {
+        let roll = rng.next_f64();
+        if roll < 0.1 {
+            StarKind::Gold
+        } else if roll < 0.4 {
+            StarKind::Silver
+        } else {
+            StarKind::Bronze
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        match self {
+            StarKind::Bronze => 1,
+            StarKind::Silver => 3,
+            StarKind::Gold => 5,
+        }
+    }
+
+    fn sprite(&self) -> Sprite {
+        match self {
+            StarKind::Bronze => Sprite::StarBronze,
+            StarKind::Silver => Sprite::StarSilver,
+            StarKind::Gold => Sprite::StarGold,
+        }
+    }
 }
+
+/// Results screen, drawn over a frozen copy of the scene the plane crashed in.
 pub struct GameOver{
-    frames: u8,
+    score: u32,
+    star_total: u32,
+    rank: Option<usize>,
+    medal: Option<Medal>,
+    elapsed: f64,
+
+    plane_position: Position,
+    plane_time: f64,
+    plane_angle: f64,
+    obstacles: Vec<Obstacle>,
+    stars: Vec<Star>,
+    terrain_offset: f64,
+    distance: f64,
+    particles: Emitter,
 }
 
 impl GameState for Waiting {
-    fn update(&mut self, delta: &f64, input: &bool) -> Option<Box<dyn GameState>>{
-        if *input {
-            Some(Box::new(GetReady{ scroll_speed: 1.0, time_elapsed: 0.0}))
+    fn update(&mut self, session: &mut Session, _delta: &f64, input: &InputState) -> Option<Box<dyn GameState>>{
+        if input.any_pressed(&[Action::Flap, Action::Confirm]) {
+            Some(Box::new(Hangar::new(session)))
         } else {
             None
         }
     }
     
-    fn draw(&self, renderer: &Renderer, image: &HtmlImageElement, sheet: &Spritesheet){
-        clear_canvas(renderer);
+    fn draw(&self, session: &Session, renderer: &Renderer, images: &[Image], sprites: &Sprites, _fonts: &Fonts, _alpha: &f64){
+        let canvas = &session.config.canvas;
+        clear_canvas(canvas, renderer);
 
-        let plane_sprite = sheet.tileset.get("planeRed1.png").unwrap();
+        let plane_sprite = sprites.plane(session.skin).frame_at(0.0);
 
-        let h_pos = CANVAS_WIDTH/2.0 - (plane_sprite.width as f64)/2.0;
-        let v_pos = CANVAS_HEIGHT/2.0 - (plane_sprite.height as f64)/2.0;
+        let h_pos = canvas.width as f64/2.0 - (plane_sprite.width() as f64)/2.0;
+        let v_pos = canvas.height as f64/2.0 - (plane_sprite.height() as f64)/2.0;
 
-        draw_background(sheet, image, renderer);
-        draw_limits(0, sheet, image, renderer);
-        draw_plane("Red", &1_u16, &Position { x: h_pos, y: v_pos }, sheet, image, renderer);
+        draw_background(canvas, &1.0, sprites, images, renderer);
+        draw_terrain(&session.config.biomes.at(0.0), 0, canvas, sprites, images, renderer);
+        draw_plane(session.skin, 0.0, &SpriteDraw::at(h_pos, v_pos), sprites, images, renderer);
             
-        let tap_left_sprite = sheet.tileset
-            .get("tapLeft.png")
-            .unwrap();
-        let tap_right_sprite = sheet.tileset
-            .get("tapRight.png")
-            .unwrap();
-        let offset = 8;
-
-        renderer.draw_image(
-            image, 
+        let tap_left_sprite = &sprites[Sprite::TapLeft];
+        let tap_right_sprite = &sprites[Sprite::TapRight];
+        let offset = 8.0;
+
+        renderer.draw_sheet_sprite(
+            images, 
             tap_right_sprite, 
-            &Rect { 
-                x: h_pos as i32 - tap_right_sprite.width - offset, 
-                y: 480/2 - tap_right_sprite.height/2, 
-                width: tap_right_sprite.width, 
-                height: tap_right_sprite.height 
-            }
+            &SpriteDraw { origin: Position { x: 1.0, y: 0.5 }, ..SpriteDraw::at(h_pos - offset, canvas.height as f64/2.0) }
         );
 
-        renderer.draw_image(
-            image, 
+        renderer.draw_sheet_sprite(
+            images, 
             tap_left_sprite, 
-            &Rect { 
-                x: h_pos as i32 + tap_left_sprite.width + offset, 
-                y: 480/2 - tap_left_sprite.height/2, 
-                width: tap_left_sprite.width, 
-                height: tap_left_sprite.height 
-            }
+            &SpriteDraw { origin: Position { x: 0.0, y: 0.5 }, ..SpriteDraw::at(h_pos + (tap_left_sprite.width() as f64) + offset, canvas.height as f64/2.0) }
         );
     }
 }
 
+impl Hangar {
+    fn new(session: &Session) -> Self {
+        Hangar { selected: session.skin }
+    }
+}
+
+impl GameState for Hangar {
+    fn update(&mut self, session: &mut Session, _delta: &f64, input: &InputState) -> Option<Box<dyn GameState>>{
+        let skins = &session.config.skins;
+        let third = session.config.canvas.width as f64 / 3.0;
+        let pointed = input.is_pressed(Action::Point);
+
+        if input.is_pressed(Action::Left) || (pointed && input.pointer.x < third) {
+            self.selected = (self.selected + skins.len() - 1) % skins.len();
+        } else if input.is_pressed(Action::Right) || (pointed && input.pointer.x > third * 2.0) {
+            self.selected = (self.selected + 1) % skins.len();
+        } else if (pointed || input.is_pressed(Action::Confirm)) && skins[self.selected].is_unlocked(session.scores.best()) {
+            session.skin = self.selected;
+            return Some(Box::new(GetReady{ time_elapsed: 0.0 }));
+        }
+
+        None
+    }
+
+    fn draw(&self, session: &Session, renderer: &Renderer, images: &[Image], sprites: &Sprites, fonts: &Fonts, _alpha: &f64){
+        let canvas = &session.config.canvas;
+        clear_canvas(canvas, renderer);
+
+        let skin = &session.config.skins[self.selected];
+        let unlocked = skin.is_unlocked(session.scores.best());
+        let plane_sprite = sprites.plane(self.selected).frame_at(0.0);
+
+        let h_pos = canvas.width as f64/2.0 - (plane_sprite.width() as f64)/2.0;
+        let v_pos = canvas.height as f64/2.0 - (plane_sprite.height() as f64)/2.0;
+
+        draw_background(canvas, &1.0, sprites, images, renderer);
+        draw_terrain(&session.config.biomes.at(0.0), 0, canvas, sprites, images, renderer);
+
+        let alpha = if unlocked { 1.0 } else { 0.4 };
+        draw_plane(self.selected, 0.0, &SpriteDraw { alpha, ..SpriteDraw::at(h_pos, v_pos) }, sprites, images, renderer);
+
+        fonts.sprite.draw(
+            &skin.color, 
+            &Position { x: canvas.width as f64/2.0, y: v_pos - 60.0 }, 
+            &TextStyle { scale: 0.5, align: Align::Center, ..TextStyle::default() }, 
+            renderer, 
+            images
+        );
+        if !unlocked {
+            fonts.label.draw(
+                &format!("Score {} to unlock", skin.unlock_score), 
+                &Position { x: canvas.width as f64/2.0, y: v_pos + plane_sprite.height() as f64 + 20.0 }, 
+                &TextStyle { align: Align::Center, max_width: Some(canvas.width as f64/3.0), ..TextStyle::default() }, 
+                renderer
+            );
+        }
+
+        for (sprite, center_x) in [(Sprite::TapLeft, canvas.width/6), (Sprite::TapRight, canvas.width*5/6)] {
+            let sprite = &sprites[sprite];
+            renderer.draw_sheet_sprite(
+                images, 
+                sprite, 
+                &SpriteDraw::centered(center_x as f64, canvas.height as f64/2.0)
+            );
+        }
+    }
+}
+
 impl GameState for GetReady {
-    fn update(&mut self, delta: &f64, input: &bool) -> Option<Box<dyn GameState>>{
+    fn update(&mut self, session: &mut Session, delta: &f64, _input: &InputState) -> Option<Box<dyn GameState>>{
         self.time_elapsed += delta;
 
-        if self.time_elapsed as u16 >= 4 {
-            Some(
-                Box::new(
-                    Playing{
-                        plane_frame: 1.0,
-                        scroll_speed: self.scroll_speed, 
-                        terrain_offset: 0.0,
-                        obstacles: Vec::new(), 
-                        distance_between_obstacles: 400.0,
-                        world: World::default(),
-                        plane_collider: None,
-                    }
-                )
-            )
-            // Some(Box::new(GameOver))
+        if self.time_elapsed >= session.config.countdown {
+            Some(Box::new(Playing::new(session)))
         } else {
             None
         }
     }
     
-    fn draw(&self, renderer: &Renderer, image: &HtmlImageElement, sheet: &Spritesheet){
-        clear_canvas(renderer);
+    fn draw(&self, session: &Session, renderer: &Renderer, images: &[Image], sprites: &Sprites, _fonts: &Fonts, _alpha: &f64){
+        let canvas = &session.config.canvas;
+        clear_canvas(canvas, renderer);
         
-        let index: usize = usize::from(self.time_elapsed as u16);
-        let sprite_name: Vec<String> = vec![
-            String::from("textGetReady.png"), 
-            String::from("number3.png"), 
-            String::from("number2.png"), 
-            String::from("number1.png")
-        ];
+        // "Get ready" followed by 3, 2, 1, each shown for a quarter of the countdown
+        let progress = self.time_elapsed / session.config.countdown;
+        let index: usize = ((progress * 4.0) as usize).min(3);
+        let shown = [Sprite::TextGetReady, Sprite::Number3, Sprite::Number2, Sprite::Number1];
 
-        let sprite = sheet.tileset
-            .get(sprite_name.get(index).unwrap())
-            .unwrap();
+        let plane_sprite = sprites.plane(session.skin).frame_at(0.0);
 
-        let plane_sprite = sheet.tileset.get("planeRed1.png").unwrap();
+        let start_pos = canvas.width as f64/2.0 - (plane_sprite.width() as f64)/2.0;
+        let end_pos = plane_sprite.width() as f64;
 
-        let start_pos = CANVAS_WIDTH/2.0 - (plane_sprite.width as f64)/2.0;
-        let end_pos = plane_sprite.width as f64;
+        let h_pos = start_pos - (start_pos - end_pos) * progress;
+        let v_pos = canvas.height as f64/2.0 - (plane_sprite.height() as f64)/2.0;
 
-        let h_pos = start_pos - ((start_pos - end_pos) * self.time_elapsed) / 4.0;
-        let v_pos = CANVAS_HEIGHT/2.0 - (plane_sprite.height as f64)/2.0;
-
-        draw_background(sheet, image, renderer);
-        draw_limits(0, sheet, image, renderer);
+        draw_background(canvas, &1.0, sprites, images, renderer);
+        draw_terrain(&session.config.biomes.at(0.0), 0, canvas, sprites, images, renderer);
         
-        draw_plane("Red", &1_u16, &Position { x: h_pos, y: v_pos }, sheet, image, renderer);
+        draw_plane(session.skin, 0.0, &SpriteDraw::at(h_pos, v_pos), sprites, images, renderer);
 
-        renderer.draw_image(
-            image, 
-            sprite, 
+        draw_centered(shown[index], canvas, sprites, images, renderer);
+    }
+}
+
+impl Paused {
+    pub fn new(run: Box<dyn GameState>) -> Self {
+        Paused { run: Some(run), resuming: None }
+    }
+}
+
+impl GameState for Paused {
+    fn update(&mut self, _session: &mut Session, delta: &f64, input: &InputState) -> Option<Box<dyn GameState>>{
+        let pause = input.is_pressed(Action::Pause);
+
+        match self.resuming.as_mut() {
+            // Pausing or losing focus again mid-countdown starts the wait over
+            Some(_) if pause || input.is_pressed(Action::Suspend) => self.resuming = None,
+            Some(elapsed) => {
+                *elapsed += delta;
+                if *elapsed >= RESUME_COUNTDOWN {
+                    return self.run.take();
+                }
+            }
+            None if pause || input.any_pressed(&[Action::Flap, Action::Confirm]) => self.resuming = Some(0.0),
+            None => {}
+        }
+
+        None
+    }
+
+    fn draw(&self, session: &Session, renderer: &Renderer, images: &[Image], sprites: &Sprites, fonts: &Fonts, _alpha: &f64){
+        let Some(run) = self.run.as_ref() else { return };
+        let canvas = &session.config.canvas;
+
+        // Nothing moves while paused, so the run is drawn exactly where its last tick left it
+        run.draw(session, renderer, images, sprites, fonts, &1.0);
+
+        if let Some(elapsed) = self.resuming {
+            let remaining = (RESUME_COUNTDOWN - elapsed).max(0.0);
+            let number = ((remaining / RESUME_COUNTDOWN * 3.0).ceil() as u32).clamp(1, 3);
+            draw_centered(Sprite::countdown(number), canvas, sprites, images, renderer);
+            return;
+        }
+
+        draw_background(canvas, &0.6, sprites, images, renderer);
+
+        fonts.sprite.draw(
+            "Paused", 
+            &Position { x: canvas.width as f64/2.0, y: canvas.height as f64/2.0 - 60.0 }, 
+            &TextStyle { scale: 0.8, align: Align::Center, ..TextStyle::default() }, 
+            renderer, 
+            images
+        );
+        fonts.label.draw(
+            "Tap to resume", 
+            &Position { x: canvas.width as f64/2.0, y: canvas.height as f64/2.0 + 20.0 }, 
+            &TextStyle { align: Align::Center, ..TextStyle::default() }, 
+            renderer
+        );
+    }
+
+    fn score(&self) -> Option<u32> {
+        self.run.as_ref().and_then(|run| run.score())
+    }
+}
+
+impl Playing {
+    fn new(session: &mut Session) -> Self {
+        let seed = session.next_run_seed();
+
+        Playing {
+            seed,
+            rng: Rng::new(seed),
+            plane_time: 0.0,
+            distance: 0.0,
+            difficulty: session.config.difficulty.at(0.0),
+            terrain_offset: 0.0,
+            last_scroll: 0.0,
+            obstacles: Vec::new(), 
+            score: 0,
+            stars: Vec::new(),
+            star_total: 0,
+            particles: Emitter::new(PARTICLE_CAPACITY, seed),
+            exhaust_timer: 0.0,
+            flap_cooldown: 0.0,
+            flap_hold: 0.0,
+            world: World::new(session.config.physics.gravity),
+            plane_collider: None,
+            previous_plane_position: None,
+            plane_angle: 0.0,
+            previous_plane_angle: 0.0,
+        }
+    }
+
+    /// Current scroll speed in px/s.
+    fn speed(&self, config: &GameConfig) -> f64 {
+        config.scroll_speed * self.difficulty.scroll_speed
+    }
+
+    fn spawn_obstacle(&mut self, session: &Session, min_x: f64, max_offset: f64) {
+        let (position, pointing_up) = create_obstacle(
+            &mut self.rng, 
+            &session.config.canvas, 
+            min_x, 
+            max_offset, 
+            &self.difficulty.vertical_offset
+        );
+        let biome = session.config.biomes.dominant_index(self.distance);
+        let rocks = session.outlines.biome(biome);
+        let outline = if pointing_up { &rocks.rock } else { &rocks.rock_down };
+        let body = self.world.add_obstacle(
             &Rect { 
-                x: CANVAS_WIDTH as i32/2 - sprite.width/2, 
-                y: CANVAS_HEIGHT as i32/2 - sprite.height/2, 
-                width: sprite.width, 
-                height: sprite.height 
+                x: position.x as i32, 
+                y: position.y as i32, 
+                width: ROCK_SIZE.width, 
+                height: ROCK_SIZE.height 
+            }, 
+            pointing_up, 
+            self.speed(&session.config),
+            outline.as_deref()
+        );
+
+        if let Some(previous) = self.obstacles.last().map(|obstacle| (obstacle.position, obstacle.pointing_up)) {
+            self.spawn_star(session, &previous, &(position, pointing_up));
+        }
+        self.obstacles.push(Obstacle { body, position, passed: false, biome, pointing_up });
+    }
+
+    /// Puts a star halfway between two rocks, level with the middle of their openings.
+    /// Each rock comes with whether it points up.
+    fn spawn_star(&mut self, session: &Session, previous: &(Position, bool), next: &(Position, bool)) {
+        let config = &session.config;
+        let opening_center = |&(rock, pointing_up): &(Position, bool)| {
+            if pointing_up {
+                (config.ground_height + rock.y) / 2.0
+            } else {
+                (rock.y + ROCK_SIZE.height as f64 + config.canvas.height as f64 - config.ground_height) / 2.0
             }
+        };
+
+        let center = Position {
+            x: (previous.0.x + ROCK_SIZE.width as f64 + next.0.x) / 2.0,
+            y: (opening_center(previous) + opening_center(next)) / 2.0,
+        };
+        let position = Position { 
+            x: center.x - STAR_SIZE.width as f64/2.0, 
+            y: center.y - STAR_SIZE.height as f64/2.0 
+        };
+        let body = self.world.add_star(
+            &Rect { 
+                x: position.x as i32, 
+                y: position.y as i32, 
+                width: STAR_SIZE.width, 
+                height: STAR_SIZE.height 
+            }, 
+            self.speed(config)
         );
+
+        self.stars.push(Star { body, position, kind: StarKind::pick(&mut self.rng) });
     }
 }
 
 impl GameState for Playing {
-    fn update(&mut self, delta: &f64, input: &bool) -> Option<Box<dyn GameState>>{
-        self.world.update();
+    fn update(&mut self, session: &mut Session, delta: &f64, input: &InputState) -> Option<Box<dyn GameState>>{
+        self.previous_plane_position = self.plane_collider
+            .map(|handle| self.world.get_body_position(&handle));
+        self.previous_plane_angle = self.plane_angle;
+        self.world.update(*delta);
+
+        let config = &session.config;
+        let canvas = &config.canvas;
 
         if self.plane_collider.is_none() {
+            let ground_height = config.ground_height as i32;
             self.world.add_collider(&Rect { 
                 x: 0, 
-                y: 480 - 71, 
-                width: 808,
-                height: 71 
+                y: canvas.height - ground_height, 
+                width: canvas.width,
+                height: ground_height 
             });
             self.world.add_collider(&Rect{
                 x: 0,
                 y: 0,
-                width: 808,
-                height: 71,
+                width: canvas.width,
+                height: ground_height,
             });
 
             let handle = self.world.add_plane(
                 &Rect{ 
-                    x: 88, 
-                    y: (CANVAS_HEIGHT/2.0 - 73.0/2.0) as i32, 
-                    width: 88, 
-                    height: 73
-                }
+                    x: PLANE_X as i32, 
+                    y: canvas.height/2 - PLANE_SIZE.height/2, 
+                    width: PLANE_SIZE.width, 
+                    height: PLANE_SIZE.height
+                },
+                session.outlines.plane(session.skin)
             );
 
             self.plane_collider = Some(handle);
         }
 
-        if *input {
-            self.world.add_impulse(self.plane_collider.as_ref().unwrap(), -50_000.0);
+        let plane = self.plane_collider.unwrap();
+        let plane_center = self.world.get_body_position(&plane);
+        // Puffs drift with the terrain rather than following the plane
+        let drift = Position { x: -self.speed(config), y: 0.0 };
+        self.flap_cooldown = (self.flap_cooldown - delta).max(0.0);
+        // Only fresh presses flap, a finger still down when the countdown ends does not
+        if input.is_pressed(Action::Flap) && self.flap_cooldown <= 0.0 {
+            let physics = &config.physics;
+            if physics.flap_resets_velocity {
+                self.world.set_vertical_velocity(&plane, 0.0);
+            }
+            self.world.add_impulse(&plane, physics.flap_impulse as f32);
+            self.flap_cooldown = physics.flap_cooldown;
+            self.flap_hold = physics.flap_hold.as_ref().map_or(0.0, |hold| hold.duration);
+
+            let below = Position { x: plane_center.x, y: plane_center.y + PLANE_SIZE.height as f64/2.0 };
+            self.particles.emit(&TAP_BURST, &below, &drift, 6);
+        }
+        if !input.is_held(Action::Flap) {
+            self.flap_hold = 0.0;
+        }
+        if let Some(hold) = config.physics.flap_hold.as_ref().filter(|_| self.flap_hold > 0.0) {
+            self.world.accelerate(&plane, hold.acceleration, *delta);
+            self.flap_hold -= delta;
         }
 
-        let plane_rotation_speed = 60.0 / 3.0;
-        self.plane_frame += delta * plane_rotation_speed;
-        self.plane_frame = self.plane_frame % 3.0;
+        self.exhaust_timer += delta;
+        while self.exhaust_timer >= EXHAUST_INTERVAL {
+            self.exhaust_timer -= EXHAUST_INTERVAL;
+            let tail = Position { x: plane_center.x - PLANE_SIZE.width as f64/2.0, y: plane_center.y };
+            self.particles.emit(&EXHAUST, &tail, &drift, 1);
+        }
+        self.particles.update(delta);
+
+        let biome = config.biomes.dominant(self.distance);
+        self.world.set_drag(&plane, biome.drag);
+        if let Some(gust) = biome.gust.as_ref() {
+            self.world.accelerate(&plane, gust.acceleration(self.distance), *delta);
+        }
 
-        self.terrain_offset -= delta * 100.0 * self.scroll_speed ;
-        self.terrain_offset = self.terrain_offset % 808.0;
+        // The collider turns with the sprite, so what you see is what hits the rocks
+        let vertical_speed = self.world.get_body_velocity(&plane).y;
+        self.plane_angle = config.physics.tilt.ease(self.plane_angle, vertical_speed, *delta);
+        self.world.set_rotation(&plane, self.plane_angle);
 
-        for pos in self.obstacles.iter_mut() {
-            pos.x -= delta * 100.0 * self.scroll_speed;  
+        self.plane_time += delta;
+
+        self.last_scroll = delta * self.speed(config);
+        self.terrain_offset -= self.last_scroll;
+        self.terrain_offset %= TERRAIN_WIDTH;
+        self.distance += self.last_scroll;
+        self.difficulty = config.difficulty.at(self.distance);
+
+        let speed = self.speed(config);
+        for obstacle in self.obstacles.iter_mut() {
+            // Rocks already on screen keep up with the terrain as the curve speeds it up
+            self.world.set_scroll_speed(&obstacle.body, speed);
+            let center = self.world.get_body_position(&obstacle.body);
+            obstacle.position = Position { 
+                x: center.x - ROCK_SIZE.width as f64/2.0, 
+                y: center.y - ROCK_SIZE.height as f64/2.0 
+            };
+        }
+        for star in self.stars.iter_mut() {
+            self.world.set_scroll_speed(&star.body, speed);
+            let center = self.world.get_body_position(&star.body);
+            star.position = Position { 
+                x: center.x - STAR_SIZE.width as f64/2.0, 
+                y: center.y - STAR_SIZE.height as f64/2.0 
+            };
         }
 
-        self.obstacles.retain_mut(|pos| pos.x > -200.0);
+        let world = &mut self.world;
+        self.obstacles.retain(|obstacle| {
+            let visible = obstacle.position.x > -200.0;
+            if !visible {
+                world.remove_body(&obstacle.body);
+            }
+            visible
+        });
+        self.stars.retain(|star| {
+            let visible = star.position.x > -200.0;
+            if !visible {
+                world.remove_body(&star.body);
+            }
+            visible
+        });
         
-        if self.obstacles.len() == 0 {
-            self.obstacles.push(create_obstacle(CANVAS_WIDTH, 0.0));
+        if self.obstacles.is_empty() {
+            self.spawn_obstacle(session, canvas.width as f64, 0.0);
         } else {
-            let last_obstacle = self.obstacles.last().unwrap();
-            if last_obstacle.x <= CANVAS_WIDTH - self.distance_between_obstacles {
-                self.obstacles.push(create_obstacle(last_obstacle.x + self.distance_between_obstacles, 200.0));
+            let last_obstacle = &self.obstacles.last().unwrap().position;
+            let distance_between_obstacles = self.difficulty.distance_between_obstacles;
+            if last_obstacle.x <= canvas.width as f64 - distance_between_obstacles {
+                let min_x = last_obstacle.x + distance_between_obstacles;
+                self.spawn_obstacle(session, min_x, 200.0);
             }
         }
 
         let mut is_game_over = false;
+        let mut plane_position = Position { x: 0.0, y: 0.0 };
 
-        if let Some(handle) = self.plane_collider.as_ref() {
-            let pos = self.world.get_body_position(handle);
-
-            is_game_over = pos.y - 73.0/2.0 < 71.0 || pos.y + 73.0/2.0 > CANVAS_HEIGHT - 71.0;
-
-            let value_in_range = |value, min, max| (value >= min) && (value <= max); 
-            for obstacle in self.obstacles.iter() {                
-                let x_overlap = 
-                value_in_range(
-                    pos.x - 88.0/2.0, 
-                    obstacle.x, 
-                    obstacle.x + 108.0
-                ) || value_in_range(
-                    pos.x + 88.0/2.0, 
-                    obstacle.x, 
-                    obstacle.x + 108.0
-                );
-
-                let y_overlap = 
-                value_in_range(
-                    pos.y - 73.0/2.0,
-                    obstacle.y,
-                    obstacle.y + 239.0
-                ) || value_in_range(
-                    pos.y + 73.0/2.0,
-                    obstacle.y,
-                    obstacle.y + 239.0
-                );
-                
-                is_game_over = is_game_over || (x_overlap && y_overlap);
+        if let Some(handle) = self.plane_collider {
+            let pos = self.world.get_body_position(&handle);
+            plane_position = pos;
+
+            for obstacle in self.obstacles.iter_mut() {
+                if !obstacle.passed && obstacle.position.x + (ROCK_SIZE.width as f64) < pos.x - PLANE_SIZE.width as f64/2.0 {
+                    obstacle.passed = true;
+                    self.score += 1;
+                }
+            }
+
+            for event in self.world.collision_events().iter().filter(|event| event.started()) {
+                let bodies = [event.collider1(), event.collider2()].map(|collider| self.world.collider_parent(&collider));
+                let other = match bodies {
+                    [Some(first), other] if first == handle => other,
+                    [other, Some(second)] if second == handle => other,
+                    _ => continue,
+                };
+
+                match self.stars.iter().position(|star| Some(star.body) == other) {
+                    Some(index) => {
+                        let star = self.stars.remove(index);
+                        self.world.remove_body(&star.body);
+                        self.star_total += star.kind.value();
+                    },
+                    None => is_game_over = true,
+                }
             }
         }
 
         if is_game_over {
-            Some(Box::new(GameOver{frames: 0}))
+            self.particles.emit(&CRASH, &plane_position, &Position::default(), 16);
+
+            let rank = session.scores.submit(ScoreEntry {
+                date: utils::timestamp(),
+                score: self.score,
+                plane_color: session.skin().color.clone(),
+                seed: self.seed,
+                stars: self.star_total,
+            });
+            // Failing to persist should not end the session, the table still holds this run
+            let _ = session.scores.save();
+
+            Some(Box::new(GameOver{
+                score: self.score, 
+                star_total: self.star_total,
+                rank, 
+                medal: session.config.medals.award(self.score),
+                elapsed: 0.0,
+                plane_position,
+                plane_time: self.plane_time,
+                plane_angle: self.plane_angle,
+                obstacles: self.obstacles.clone(),
+                stars: self.stars.clone(),
+                terrain_offset: self.terrain_offset,
+                distance: self.distance,
+                particles: self.particles.clone(),
+            }))
         } else {
             None
         }
     }
     
-    fn draw(&self, renderer: &Renderer, image: &HtmlImageElement, sheet: &Spritesheet){
-        clear_canvas(renderer);
+    fn draw(&self, session: &Session, renderer: &Renderer, images: &[Image], sprites: &Sprites, fonts: &Fonts, alpha: &f64){
+        let canvas = &session.config.canvas;
+        clear_canvas(canvas, renderer);
         
-        let plane_sprite = sheet.tileset.get("planeRed1.png").unwrap();
+        // Everything scrolls at the same rate, so rewinding by the part of the
+        // last tick we have not reached yet interpolates the whole scene
+        let scroll_lag = self.last_scroll * (1.0 - alpha);
 
-        let h_pos = plane_sprite.width as f64;
-        let v_pos = CANVAS_HEIGHT/2.0 - (plane_sprite.height as f64)/2.0;
-
-        draw_background(sheet, image, renderer);
+        draw_background(canvas, &1.0, sprites, images, renderer);
+        self.particles.draw(scroll_lag, renderer, images, sprites);
 
         if let Some(handle) = self.plane_collider.as_ref() {
-            let pos = self.world.get_body_position(handle);
+            let current = self.world.get_body_position(handle);
+            let pos = match self.previous_plane_position {
+                Some(previous) => Position {
+                    x: lerp(previous.x, current.x, *alpha),
+                    y: lerp(previous.y, current.y, *alpha),
+                },
+                None => current,
+            };
             draw_plane(
-                "Red", 
-                &(self.plane_frame as u16 + 1), 
-                &Position{x: pos.x - plane_sprite.width as f64/2.0, y: pos.y - plane_sprite.height as f64/2.0}, 
-                sheet, 
-                image, 
+                session.skin, 
+                self.plane_time, 
+                &SpriteDraw { 
+                    rotation: lerp(self.previous_plane_angle, self.plane_angle, *alpha), 
+                    ..SpriteDraw::centered(pos.x, pos.y) 
+                }, 
+                sprites, 
+                images, 
                 renderer
             );
         }
         draw_obstacles(
             &self.obstacles, 
-            sheet, 
-            image, 
+            scroll_lag,
+            sprites, 
+            images, 
+            renderer
+        );
+        draw_stars(&self.stars, scroll_lag, sprites, images, renderer);
+        let terrain_offset = -(-self.terrain_offset - scroll_lag).rem_euclid(TERRAIN_WIDTH);
+        draw_terrain(
+            &session.config.biomes.at(self.distance - scroll_lag), 
+            terrain_offset as i32, 
+            canvas, 
+            sprites, 
+            images, 
+            renderer
+        );
+        fonts.sprite.draw(
+            &self.score.to_string(), 
+            &Position { x: canvas.width as f64/2.0, y: session.config.ground_height + 10.0 }, 
+            &TextStyle { align: Align::Center, ..TextStyle::default() }, 
+            renderer, 
+            images
+        );
+        draw_star_total(
+            self.star_total, 
+            &Position { x: 16.0, y: session.config.ground_height + 10.0 }, 
+            sprites, 
+            &fonts.sprite, 
+            images, 
             renderer
         );
-        draw_limits(self.terrain_offset as i32, sheet, image, renderer);
+    }
+
+    fn score(&self) -> Option<u32> {
+        Some(self.score)
+    }
+
+    fn pausable(&self) -> bool {
+        true
     }
 }
 
 impl GameState for GameOver {
-    fn update(&mut self, delta: &f64, input: &bool) -> Option<Box<dyn GameState>>{
-        self.frames += if self.frames > 1 { 0 } else { 1 };
+    fn update(&mut self, _session: &mut Session, delta: &f64, input: &InputState) -> Option<Box<dyn GameState>>{
+        self.elapsed += delta;
+        self.particles.update(delta);
 
-        if *input {
+        // Taps only count once the panel has settled, so a late flap does not skip it
+        if input.any_pressed(&[Action::Flap, Action::Confirm]) && self.elapsed >= PANEL_ANIMATION_TIME {
             Some(
                 Box::new(
                     GetReady{
-                        scroll_speed: 1.0, 
                         time_elapsed: 0.0
                     }
                 )
@@ -300,35 +843,83 @@ impl GameState for GameOver {
         }
     }
 
-    fn draw(&self, renderer: &Renderer, image: &HtmlImageElement, sheet: &Spritesheet){
-        if self.frames < 1 {
-            let sprite = sheet.tileset.get("textGameOver.png").unwrap();
+    fn draw(&self, session: &Session, renderer: &Renderer, images: &[Image], sprites: &Sprites, fonts: &Fonts, _alpha: &f64){
+        let canvas = &session.config.canvas;
+        clear_canvas(canvas, renderer);
 
-            renderer.draw_image(
-                image, 
-                sprite, 
-                &Rect { 
-                    x: CANVAS_WIDTH as i32/2 - sprite.width/2, 
-                    y: CANVAS_HEIGHT as i32/2 - sprite.height/2, 
-                    width: sprite.width, 
-                    height: sprite.height 
-                }
-            );
-        }
+        draw_background(canvas, &1.0, sprites, images, renderer);
+        draw_plane(
+            session.skin, 
+            self.plane_time, 
+            &SpriteDraw { 
+                rotation: self.plane_angle, 
+                ..SpriteDraw::centered(self.plane_position.x, self.plane_position.y) 
+            }, 
+            sprites, 
+            images, 
+            renderer
+        );
+        draw_obstacles(&self.obstacles, 0.0, sprites, images, renderer);
+        draw_stars(&self.stars, 0.0, sprites, images, renderer);
+        draw_terrain(
+            &session.config.biomes.at(self.distance), 
+            self.terrain_offset as i32, 
+            canvas, 
+            sprites, 
+            images, 
+            renderer
+        );
+        self.particles.draw(0.0, renderer, images, sprites);
+
+        let progress = (self.elapsed / PANEL_ANIMATION_TIME).min(1.0);
+        let eased = 1.0 - (1.0 - progress).powi(3);
+
+        let title = &sprites[Sprite::TextGameOver];
+        renderer.draw_sheet_sprite(
+            images, 
+            title, 
+            &SpriteDraw { origin: Position { x: 0.5, y: 0.0 }, ..SpriteDraw::at(canvas.width as f64/2.0, lerp(-title.height() as f64, 76.0, eased)) }
+        );
+
+        let panel = &sprites[Sprite::Panel];
+        let panel_position = Position {
+            x: canvas.width as f64/2.0 - panel.width() as f64/2.0,
+            y: lerp(canvas.height as f64, 164.0, eased),
+        };
+        draw_results_panel(self, &panel_position, session.scores.best(), sprites, &fonts.sprite, images, renderer);
     }
+
+    fn score(&self) -> Option<u32> {
+        Some(self.score)
+    }
+}
+
+/// Sprite letters for the big numbers and titles, the outline font for small print.
+pub struct Fonts {
+    pub sprite: SpriteFont,
+    pub label: TrueTypeFont,
 }
 
 pub struct TappyPlane{
-    pub image: Option<HtmlImageElement>,
-    pub sheet: Option<Spritesheet>,
+    /// Atlas images in the order of `Spritesheet::images`.
+    pub images: Vec<Image>,
+    pub sprites: Option<Sprites>,
+    pub fonts: Option<Fonts>,
+    pub session: Session,
     pub state: Box<dyn GameState>,
 }
 
-impl Default for TappyPlane {
-    fn default() -> Self {
+impl TappyPlane {
+    /// Starts a game whose high scores only live in memory, `init` swaps in persistent ones.
+    pub fn new(seed: u64) -> Self {
+        let scores = HighScores::load(Box::<MemoryStore>::default(), HIGH_SCORE_CAPACITY)
+            .expect("An empty in-memory score table always loads");
+
         TappyPlane { 
-            image: None, 
-            sheet: None, 
+            images: Vec::new(), 
+            sprites: None, 
+            fonts: None,
+            session: Session::new(seed, GameConfig::default(), scores, ColliderOutlines::new()),
             state: Box::new(Waiting),
         }
     }
@@ -337,165 +928,404 @@ impl Default for TappyPlane {
 #[async_trait(?Send)]
 impl Game for TappyPlane {
     async fn init(&self) -> Result<Box<dyn Game>> {
-        let sheet: Spritesheet = serde_wasm_bindgen::from_value(
-            browser::fetch_json("/assets/sheet.json")
-            .await?
-        ).unwrap();
+        let mut sheet = Spritesheet::default();
+        for source in SHEETS {
+            sheet = sheet.merge(atlas::read(&browser::fetch_text(source).await?, source)?)?;
+        }
 
-        let image = engine::load_image(&sheet.image).await?;
+        let mut images = Vec::new();
+        for source in sheet.images.iter() {
+            images.push(Image::Element(engine::load_image(source).await?));
+        }
+        let config = GameConfig::from_json(&browser::fetch_text("/assets/config.json").await?)?;
+        let sprites = Sprites::resolve(&sheet, &config)?;
+        let fonts = Fonts {
+            sprite: sprite_font(&sheet)?,
+            label: label_font(&browser::fetch_bytes(LABEL_FONT).await?)?,
+        };
+        // Blocked or corrupt storage should not keep anyone from playing
+        let (scores, unreadable) = HighScores::load_or_empty(Box::new(LocalStorageStore::new(SCORES_KEY)), HIGH_SCORE_CAPACITY);
+        if let Some(err) = unreadable {
+            log!("Starting with an empty high score table: {:#}", err);
+        }
+        let colliders: ColliderOutlines = serde_wasm_bindgen::from_value(
+            browser::fetch_json("/assets/colliders.json")
+            .await?
+        ).map_err(|err| anyhow!("Could not parse collider outlines {:#?}", err))?;
 
         Ok(
             Box::new(
                 TappyPlane{
-                    image: Some(image),
-                    sheet: Some(sheet),
+                    images,
+                    sprites: Some(sprites),
+                    fonts: Some(fonts),
+                    session: Session::new(self.session.seed, config, scores, colliders),
                     state: Box::new(Waiting),
                 }
             )
         )
     }
 
-    fn update(&mut self, delta: &f64, input: &bool){
+    fn seed(&self) -> u64 {
+        self.session.seed
+    }
 
-        match self.state.update(delta, input) {
-            Some(new_state) => self.state = new_state,
-            None => ()
-        };
+    fn session_start(&self) -> SessionStart {
+        SessionStart {
+            config: self.session.config.clone(),
+            colliders: self.session.colliders.clone(),
+            scores: self.session.scores.entries().to_vec(),
+        }
     }
 
-    fn draw(&self, renderer: &Renderer) {
-        if let Some(sheet) = self.sheet.as_ref() {
-            self.image.as_ref().map(|image| {
-                self.state.draw(renderer, image, sheet);
-            });
+    /// Scores go to memory only, playing a replay back never touches the player's table.
+    fn restore(&mut self, start: &SessionStart) -> Result<()> {
+        start.config.validate()?;
+        // Skins and biomes are drawn and collided by index, a replay cannot bring new ones
+        let loaded = self.sprites.as_ref().is_none_or(|sprites| sprites.fits(&start.config))
+            && self.session.outlines.fits(&start.config);
+        if !loaded {
+            return Err(anyhow!("Replay was recorded with other skins or biomes than the game has loaded"));
+        }
+
+        let scores = HighScores::load(Box::new(MemoryStore::new(start.scores.clone())), HIGH_SCORE_CAPACITY)?;
+        self.session = Session::new(self.session.seed, start.config.clone(), scores, start.colliders.clone());
+        self.state = Box::new(Waiting);
+        Ok(())
+    }
+
+    fn score(&self) -> Option<u32> {
+        self.state.score()
+    }
+
+    fn update(&mut self, delta: &f64, input: &InputState){
+        if input.any_pressed(&[Action::Pause, Action::Suspend]) && self.state.pausable() {
+            let run = std::mem::replace(&mut self.state, Box::new(Waiting));
+            self.state = Box::new(Paused::new(run));
+            return;
+        }
+
+        if let Some(new_state) = self.state.update(&mut self.session, delta, input) {
+            self.state = new_state;
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer, alpha: &f64) {
+        if let (Some(sprites), Some(fonts)) = (self.sprites.as_ref(), self.fonts.as_ref()) {
+            self.state.draw(&self.session, renderer, &self.images, sprites, fonts, alpha);
         }
     }
 }
 
-fn clear_canvas(renderer: &Renderer) {
+/// The outline font for labels, in their size and color.
+pub fn label_font(bytes: &[u8]) -> Result<TrueTypeFont> {
+    Ok(TrueTypeFont::from_bytes(bytes)?.with_size(LABEL_SIZE).with_color(LABEL_COLOR))
+}
+
+/// The atlas letters and digits, `Sprites::resolve` reports any the sheet lacks.
+pub fn sprite_font(sheet: &Spritesheet) -> Result<SpriteFont> {
+    let font = SpriteFont::from_sheet(sheet, sprites::font_characters(), sprites::glyph_name)?;
+
+    // Slanted letters leave gaps next to each other
+    Ok(font.with_kerning(
+        [('A', 'V'), ('V', 'A'), ('A', 'W'), ('W', 'A'), ('A', 'T'), ('T', 'A'), ('A', 'Y'), ('Y', 'A'), ('L', 'T'), ('L', 'Y')]
+            .map(|pair| (pair, -6))
+    ))
+}
+
+fn clear_canvas(canvas: &Dimension, renderer: &Renderer) {
     let clear_area = Rect{
         x: 0,
         y: 0,
-        width: CANVAS_WIDTH as i32,
-        height: CANVAS_HEIGHT as i32
+        width: canvas.width,
+        height: canvas.height
     };
     renderer.clear(&clear_area);
 }
 
-fn draw_background(sheet: &Spritesheet, image: &HtmlImageElement, renderer: &Renderer) {
-    let background: &Rect = sheet.tileset.get("background.png").as_ref().unwrap();
-    renderer.draw_image(
-        &image, 
-        background, 
-        &Rect { 
-            x: 0, 
-            y: 0, 
-            width: CANVAS_WIDTH as i32, 
-            height: CANVAS_HEIGHT as i32 
-        }
+fn draw_centered(sprite: Sprite, canvas: &Dimension, sprites: &Sprites, images: &[Image], renderer: &Renderer) {
+    let sprite = &sprites[sprite];
+    renderer.draw_sheet_sprite(
+        images, 
+        sprite, 
+        &SpriteDraw::centered(canvas.width as f64/2.0, canvas.height as f64/2.0)
     );
 }
 
-fn draw_limits(offset: i32, sheet: &Spritesheet, image: &HtmlImageElement, renderer: &Renderer) {
-    let terrain_above: &Rect = sheet.tileset.get("groundDirt.png").as_ref().unwrap();
-    let terrain_below: &Rect = sheet.tileset.get("groundGrass.png").as_ref().unwrap();
-    
-    renderer.draw_image(
-        &image, 
-        terrain_below,
-        &Rect{
-            x: offset, 
-            y: CANVAS_HEIGHT as i32 - terrain_below.height,
-            width: terrain_below.width,
-            height: terrain_below.height
+fn draw_background(canvas: &Dimension, alpha: &f64, sprites: &Sprites, images: &[Image], renderer: &Renderer) {
+    let background = &sprites[Sprite::Background];
+    renderer.draw_sheet_sprite(
+        images, 
+        background, 
+        &SpriteDraw { 
+            scale: Position { 
+                x: canvas.width as f64 / background.width() as f64, 
+                y: canvas.height as f64 / background.height() as f64 
+            }, 
+            alpha: *alpha, 
+            ..SpriteDraw::default() 
         }
     );
+}
 
-    renderer.draw_image(
-        &image, 
-        terrain_below, 
-        &Rect{
-            x: offset + terrain_below.width, 
-            y: CANVAS_HEIGHT as i32 -terrain_below.height,
-            width: terrain_below.width,
-            height: terrain_below.height
-        }
-    );
+/// Draws the ground and ceiling of `blend.from`, fading in those of `blend.to` on top.
+fn draw_terrain(blend: &BiomeBlend, offset: i32, canvas: &Dimension, sprites: &Sprites, images: &[Image], renderer: &Renderer) {
+    draw_limits(offset, canvas, blend.from, &1.0, sprites, images, renderer);
 
-    renderer.context.save();
-    renderer.context.translate(terrain_above.width as f64, terrain_above.height as f64);
-    renderer.context.rotate(std::f64::consts::PI);
+    if blend.amount > 0.0 {
+        draw_limits(offset, canvas, blend.to, &blend.amount, sprites, images, renderer);
+    }
+}
 
-    renderer.draw_image(
-        &image,
-        terrain_above, 
-        &Rect{
-            x: -offset, 
-            y: 0,
-            width:terrain_above.width,
-            height: terrain_above.height,
-        }
-    );
-    renderer.draw_image(
-        &image,
-        terrain_above, 
-        &Rect{
-            x: -offset - terrain_above.width as i32, 
-            y: 0,
-            width: terrain_above.width,
-            height: terrain_above.height,
-        }
-    );
+/// Two tiles of ground along the bottom and, turned upside down, two of ceiling along the top.
+fn draw_limits(offset: i32, canvas: &Dimension, biome: usize, alpha: &f64, sprites: &Sprites, images: &[Image], renderer: &Renderer) {
+    let terrain_above = &sprites.biome(biome).ceiling;
+    let terrain_below = &sprites.biome(biome).ground;
 
-    renderer.context.restore();
+    for x in [offset, offset + terrain_below.width()] {
+        renderer.draw_sheet_sprite(
+            images, 
+            terrain_below, 
+            &SpriteDraw { 
+                alpha: *alpha, 
+                ..SpriteDraw::at(x as f64, (canvas.height - terrain_below.height()) as f64) 
+            }
+        );
+    }
+
+    for x in [offset, offset + terrain_above.width()] {
+        renderer.draw_sheet_sprite(
+            images, 
+            terrain_above, 
+            &SpriteDraw { 
+                flip_x: true, 
+                flip_y: true, 
+                alpha: *alpha, 
+                ..SpriteDraw::centered(x as f64 + terrain_above.width() as f64/2.0, terrain_above.height() as f64/2.0) 
+            }
+        );
+    }
 }
 
-fn draw_plane(color: &str, sprite_number: &u16, position: &Position, sheet: &Spritesheet, image: &HtmlImageElement, renderer: &Renderer) {
-    let plane_tile: &Rect = sheet.tileset.get(format!("plane{}{}.png", color, sprite_number ).as_str()).as_ref().unwrap();
+/// Draws the skin at `skin` `time` seconds into its propeller animation.
+fn draw_plane(skin: usize, time: f64, draw: &SpriteDraw, sprites: &Sprites, images: &[Image], renderer: &Renderer) {
+    renderer.draw_sheet_sprite(images, sprites.plane(skin).frame_at(time), draw);
+}
 
-    renderer
-        .draw_image(
-            &image,
-            plane_tile,
-            &Rect{
-                x: position.x as i32,
-                y: position.y as i32,
-                width: plane_tile.width,
-                height: plane_tile.height
-            }
+fn lerp(from: f64, to: f64, alpha: f64) -> f64 {
+    from + (to - from) * alpha
+}
+
+/// Top-left corner of a new rock and whether it stands on the ground pointing up. A
+/// negative offset moves a ceiling rock below y = 0, so the side is returned rather than
+/// read back from the position.
+fn create_obstacle(rng: &mut Rng, canvas: &Dimension, min_x: f64, max_offset: f64, offset_range: &OffsetRange)-> (Position, bool) {
+    let vertical_offset = offset_range.min + rng.next_f64() * (offset_range.max - offset_range.min);
+    let rock_height = ROCK_SIZE.height as f64;
+    let x = min_x + (rng.next_f64() * max_offset).floor();
+    let pointing_up = rng.next_f64() > 0.5;
+    let y = if pointing_up { canvas.height as f64 + vertical_offset - rock_height } else { 0.0 - vertical_offset };
+
+    (Position { x, y }, pointing_up)
+}
+
+fn draw_obstacles(obstacles: &[Obstacle], offset: f64, sprites: &Sprites, images: &[Image], renderer: &Renderer) {
+    for obstacle in obstacles.iter() {
+        let pos = &obstacle.position;
+        let biome = sprites.biome(obstacle.biome);
+        let sprite = if obstacle.pointing_up { &biome.rock } else { &biome.rock_down };
+        
+        renderer.draw_sheet_sprite(
+            images, 
+            sprite, 
+            &SpriteDraw::at(pos.x + offset, pos.y)
         );
+    }
 }
 
-fn create_obstacle(min_x: f64, max_offset: f64)-> Position {
-    let vertical_offset = js_sys::Math::random() * 239.0/2.0;
-    Position{
-        x: min_x + (js_sys::Math::random() * max_offset).floor(), 
-        y: if js_sys::Math::random() > 0.5 { CANVAS_HEIGHT + vertical_offset - 239.0 } else { 0.0 - vertical_offset } ,
+fn draw_stars(stars: &[Star], offset: f64, sprites: &Sprites, images: &[Image], renderer: &Renderer) {
+    for star in stars.iter() {
+        let sprite = &sprites[star.kind.sprite()];
+        renderer.draw_sheet_sprite(
+            images, 
+            sprite, 
+            &SpriteDraw::at(star.position.x + offset, star.position.y)
+        );
     }
 }
 
-fn draw_obstacles(obstacles: &Vec<Position>, sheet: &Spritesheet, image: &HtmlImageElement, renderer: &Renderer) {
-    for pos in obstacles.iter() {
-        let sprite = sheet
-            .tileset
-            .get(
-                if pos.y > 0.0 {
-                    "rock.png"
-                } else {
-                    "rockDown.png"
-                }
-            ).unwrap();
-        
-        renderer.draw_image(
-            image, 
+/// Star icon with the value collected next to it, `position` is the icon's top-left corner.
+fn draw_star_total(total: u32, position: &Position, sprites: &Sprites, font: &SpriteFont, images: &[Image], renderer: &Renderer) {
+    let icon = &sprites[StarKind::Gold.sprite()];
+    renderer.draw_sheet_sprite(
+        images, 
+        icon, 
+        &SpriteDraw::at(position.x, position.y)
+    );
+
+    let scale = icon.height() as f64 / font.line_height(1.0);
+    font.draw(
+        &total.to_string(), 
+        &Position { x: position.x + icon.width() as f64 + 6.0, y: position.y }, 
+        &TextStyle { scale, ..TextStyle::default() }, 
+        renderer, 
+        images
+    );
+}
+
+/// Score, best score, medal and stars laid out on the `UIbg.png` panel whose top-left corner is `position`.
+fn draw_results_panel(results: &GameOver, position: &Position, best: Option<u32>, sprites: &Sprites, font: &SpriteFont, images: &[Image], renderer: &Renderer) {
+    let panel = &sprites[Sprite::Panel];
+    renderer.draw_sheet_sprite(
+        images, 
+        panel, 
+        &SpriteDraw::at(position.x, position.y)
+    );
+
+    // The left column holds the medal, when one was earned, above the stars
+    let margin = 16.0;
+    let medal_slot = &sprites[Sprite::medal(Medal::Gold)];
+    let column_x = position.x + (margin + medal_slot.width() as f64 + panel.width() as f64)/2.0;
+
+    if let Some(medal) = results.medal {
+        let sprite = &sprites[Sprite::medal(medal)];
+        renderer.draw_sheet_sprite(
+            images, 
             sprite, 
-            &Rect{ 
-                x: pos.x.floor() as i32, 
-                y: pos.y.floor() as i32, 
-                width: sprite.width, 
-                height: sprite.height 
-            }
+            &SpriteDraw { origin: Position { x: 0.0, y: 0.5 }, ..SpriteDraw::at(position.x + margin, position.y + panel.height() as f64/2.0) }
+        );
+    }
+    draw_star_total(
+        results.star_total, 
+        &Position { x: position.x + margin, y: position.y + panel.height() as f64 - margin - STAR_SIZE.height as f64 }, 
+        sprites, 
+        font, 
+        images, 
+        renderer
+    );
+
+    let lines = [
+        ("SCORE", results.score.to_string()),
+        ("BEST", best.unwrap_or(results.score).to_string()),
+    ];
+    for (index, (label, value)) in lines.iter().enumerate() {
+        let y = position.y + 28.0 + index as f64 * 100.0;
+        font.draw(label, &Position { x: column_x, y }, &TextStyle { scale: 0.35, align: Align::Center, ..TextStyle::default() }, renderer, images);
+        font.draw(value, &Position { x: column_x, y: y + 28.0 }, &TextStyle { scale: 0.6, align: Align::Center, ..TextStyle::default() }, renderer, images);
+    }
+
+    if let Some(rank) = results.rank {
+        font.draw(
+            &format!("Rank {}", rank), 
+            &Position { x: column_x, y: position.y + panel.height() as f64 - 36.0 }, 
+            &TextStyle { scale: 0.3, align: Align::Center, ..TextStyle::default() }, 
+            renderer, 
+            images
         );
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::ActionSet;
+
+    fn session(seed: u64) -> Session {
+        let scores = HighScores::load(Box::<MemoryStore>::default(), HIGH_SCORE_CAPACITY).unwrap();
+        Session::new(seed, GameConfig::default(), scores, ColliderOutlines::new())
+    }
+
+    /// Flaps whenever the plane sinks below the middle of the screen, until the run ends
+    /// or `ticks` pass. Returns where every rock was spawned and the score.
+    fn fly(session: &mut Session, ticks: usize) -> (Vec<(f64, f64)>, u32) {
+        let mut run = Playing::new(session);
+        let mut spawned = Vec::new();
+        let mut previous = InputState::default();
+
+        for _ in 0..ticks {
+            let sinking = run.plane_collider
+                .is_some_and(|plane| run.world.get_body_position(&plane).y > session.config.canvas.height as f64 / 2.0);
+            let mut held = ActionSet::default();
+            if sinking && !previous.is_held(Action::Flap) {
+                held.insert(Action::Flap);
+            }
+            let input = InputState::new(held, &previous, Position::default());
+            previous = input;
+
+            let ended = run.update(session, &(1.0 / 60.0), &input).is_some();
+            for obstacle in run.obstacles.iter().skip(spawned.len()) {
+                spawned.push((obstacle.position.x, obstacle.position.y));
+            }
+            if ended {
+                break;
+            }
+        }
+
+        (spawned, run.score)
+    }
+
+    #[test]
+    fn same_seed_and_input_fly_the_same_run() {
+        let first = fly(&mut session(7), 1_200);
+        let second = fly(&mut session(7), 1_200);
+
+        assert!(first.0.len() > 2);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn every_run_of_a_session_flies_a_new_course() {
+        let mut session = session(7);
+        let first = fly(&mut session, 300);
+        let retry = fly(&mut session, 300);
+
+        assert!(!first.0.is_empty());
+        assert_ne!(first.0, retry.0);
+    }
+
+    fn pressing(action: Action) -> InputState {
+        let mut held = ActionSet::default();
+        held.insert(action);
+        InputState::new(held, &InputState::default(), Position::default())
+    }
+
+    #[test]
+    fn losing_focus_while_paused_does_not_resume() {
+        let mut session = session(7);
+        let mut paused = Paused::new(Box::new(Playing::new(&mut session)));
+
+        paused.update(&mut session, &(1.0 / 60.0), &pressing(Action::Suspend));
+        assert_eq!(paused.resuming, None);
+
+        paused.update(&mut session, &(1.0 / 60.0), &pressing(Action::Pause));
+        assert_eq!(paused.resuming, Some(0.0));
+    }
+
+    #[test]
+    fn pausing_or_losing_focus_mid_countdown_starts_the_wait_over() {
+        let mut session = session(7);
+        let mut paused = Paused::new(Box::new(Playing::new(&mut session)));
+
+        for action in [Action::Pause, Action::Suspend] {
+            paused.update(&mut session, &(1.0 / 60.0), &pressing(Action::Flap));
+            paused.update(&mut session, &(1.0 / 60.0), &InputState::default());
+            assert!(paused.resuming.is_some_and(|elapsed| elapsed > 0.0));
+
+            assert!(paused.update(&mut session, &(1.0 / 60.0), &pressing(action)).is_none());
+            assert_eq!(paused.resuming, None);
+        }
+    }
+
+    #[test]
+    fn ceiling_rocks_pushed_below_the_top_still_point_down() {
+        let canvas = GameConfig::default().canvas;
+        let mut rng = Rng::new(7);
+        let offsets = OffsetRange { min: -20.0, max: -10.0 };
+
+        for _ in 0..200 {
+            let (position, pointing_up) = create_obstacle(&mut rng, &canvas, 0.0, 100.0, &offsets);
+            let center = position.y + ROCK_SIZE.height as f64 / 2.0;
+            assert_eq!(pointing_up, center > canvas.height as f64 / 2.0);
+        }
+    }
+}
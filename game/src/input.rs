@@ -0,0 +1,347 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::Position;
+
+/// What the game reacts to, whichever device it came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Flap,
+    Confirm,
+    Left,
+    Right,
+    Pause,
+    /// A press on the canvas, for menus that care where it landed.
+    Point,
+    /// The page got hidden or lost focus. Only ever requested, no control is bound to it.
+    Suspend,
+}
+
+impl Action {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Actions packed into a byte, so a tick's input stays `Copy` and small in replays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ActionSet(u8);
+
+impl ActionSet {
+    pub fn contains(&self, action: Action) -> bool {
+        self.0 & action.bit() != 0
+    }
+
+    pub fn insert(&mut self, action: Action) {
+        self.0 |= action.bit();
+    }
+
+    fn union(self, other: ActionSet) -> ActionSet {
+        ActionSet(self.0 | other.0)
+    }
+
+    fn difference(self, other: ActionSet) -> ActionSet {
+        ActionSet(self.0 & !other.0)
+    }
+}
+
+/// A physical control.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    /// A `KeyboardEvent.code`, so bindings follow key positions rather than layouts.
+    Key(String),
+    /// The mouse button or a finger anywhere on the canvas.
+    Pointer,
+    /// Button index in the standard gamepad mapping.
+    GamepadButton(u32),
+}
+
+/// Which actions every control triggers. One control can drive several actions, like
+/// Space flapping in flight and confirming in menus.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionMap {
+    bindings: Vec<(Binding, Action)>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        let key = |code: &str| Binding::Key(code.to_string());
+        let mut map = ActionMap::new();
+
+        for (binding, action) in [
+            (key("Space"), Action::Flap),
+            (key("Space"), Action::Confirm),
+            (key("ArrowUp"), Action::Flap),
+            (key("Enter"), Action::Confirm),
+            (key("ArrowLeft"), Action::Left),
+            (key("ArrowRight"), Action::Right),
+            (key("Escape"), Action::Pause),
+            (key("KeyP"), Action::Pause),
+            (Binding::Pointer, Action::Flap),
+            (Binding::Pointer, Action::Point),
+            // A, d-pad up, d-pad left, d-pad right and start
+            (Binding::GamepadButton(0), Action::Flap),
+            (Binding::GamepadButton(0), Action::Confirm),
+            (Binding::GamepadButton(12), Action::Flap),
+            (Binding::GamepadButton(14), Action::Left),
+            (Binding::GamepadButton(15), Action::Right),
+            (Binding::GamepadButton(9), Action::Pause),
+        ] {
+            map.bind(binding, action);
+        }
+
+        map
+    }
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        ActionMap { bindings: Vec::new() }
+    }
+
+    pub fn bind(&mut self, binding: Binding, action: Action) {
+        if !self.bindings.iter().any(|(bound, bound_action)| *bound == binding && *bound_action == action) {
+            self.bindings.push((binding, action));
+        }
+    }
+
+    pub fn unbind(&mut self, binding: &Binding, action: Action) {
+        self.bindings.retain(|(bound, bound_action)| !(bound == binding && *bound_action == action));
+    }
+
+    /// Replaces every control bound to `action` with `bindings`.
+    pub fn rebind(&mut self, action: Action, bindings: impl IntoIterator<Item = Binding>) {
+        self.bindings.retain(|(_, bound_action)| *bound_action != action);
+        for binding in bindings {
+            self.bind(binding, action);
+        }
+    }
+
+    pub fn bindings(&self, action: Action) -> impl Iterator<Item = &Binding> {
+        self.bindings
+            .iter()
+            .filter(move |(_, bound_action)| *bound_action == action)
+            .map(|(binding, _)| binding)
+    }
+
+    pub fn is_bound(&self, binding: &Binding) -> bool {
+        self.bindings.iter().any(|(bound, _)| bound == binding)
+    }
+
+    fn actions<'a>(&self, bindings: impl Iterator<Item = &'a Binding>) -> ActionSet {
+        let mut actions = ActionSet::default();
+        for binding in bindings {
+            self.bindings
+                .iter()
+                .filter(|(bound, _)| bound == binding)
+                .for_each(|(_, action)| actions.insert(*action));
+        }
+        actions
+    }
+}
+
+/// The input of one tick, with edges worked out against the tick before.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputState {
+    held: ActionSet,
+    pressed: ActionSet,
+    released: ActionSet,
+    /// Where the pointer last went down, in canvas pixels.
+    pub pointer: Position,
+}
+
+impl InputState {
+    pub fn new(held: ActionSet, previous: &InputState, pointer: Position) -> Self {
+        InputState {
+            held,
+            pressed: held.difference(previous.held),
+            released: previous.held.difference(held),
+            pointer,
+        }
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.contains(action)
+    }
+
+    /// Whether `action` started this tick.
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(action)
+    }
+
+    /// Whether `action` stopped this tick.
+    pub fn is_released(&self, action: Action) -> bool {
+        self.released.contains(action)
+    }
+
+    pub fn any_pressed(&self, actions: &[Action]) -> bool {
+        actions.iter().any(|action| self.is_pressed(*action))
+    }
+}
+
+/// Raw state of every device, written by the browser listeners and read once per tick.
+#[derive(Debug, Default)]
+pub struct InputDevices {
+    map: ActionMap,
+    down: HashSet<Binding>,
+    /// Went down since the last tick, so a tap shorter than a tick still registers.
+    tapped: HashSet<Binding>,
+    /// Actions the page asked for, like a pause when the tab gets hidden.
+    requested: ActionSet,
+    pointer: Position,
+    previous: InputState,
+}
+
+impl InputDevices {
+    pub fn new(map: ActionMap) -> Self {
+        InputDevices { map, ..InputDevices::default() }
+    }
+
+    pub fn map(&self) -> &ActionMap {
+        &self.map
+    }
+
+    pub fn map_mut(&mut self) -> &mut ActionMap {
+        &mut self.map
+    }
+
+    pub fn press(&mut self, binding: Binding) {
+        if self.down.insert(binding.clone()) {
+            self.tapped.insert(binding);
+        }
+    }
+
+    pub fn release(&mut self, binding: &Binding) {
+        self.down.remove(binding);
+    }
+
+    /// Lets go of everything, for when the page stops hearing about releases.
+    pub fn release_all(&mut self) {
+        self.down.clear();
+    }
+
+    pub fn move_pointer(&mut self, pointer: Position) {
+        self.pointer = pointer;
+    }
+
+    /// Gamepads have no events, so their buttons are handed over in full every frame.
+    pub fn set_gamepad_buttons(&mut self, buttons: impl IntoIterator<Item = u32>) {
+        let buttons: HashSet<Binding> = buttons.into_iter().map(Binding::GamepadButton).collect();
+        self.down.retain(|binding| !matches!(binding, Binding::GamepadButton(_)) || buttons.contains(binding));
+        for button in buttons {
+            self.press(button);
+        }
+    }
+
+    /// Triggers `action` for a single tick whatever is held.
+    pub fn request(&mut self, action: Action) {
+        self.requested.insert(action);
+    }
+
+    pub fn poll(&mut self) -> InputState {
+        let held = self.map
+            .actions(self.down.iter().chain(self.tapped.iter()))
+            .union(self.requested);
+        let state = InputState::new(held, &self.previous, self.pointer);
+
+        self.tapped.clear();
+        self.requested = ActionSet::default();
+        self.previous = state;
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: &str) -> Binding {
+        Binding::Key(code.to_string())
+    }
+
+    fn held(actions: &[Action]) -> ActionSet {
+        let mut set = ActionSet::default();
+        actions.iter().for_each(|action| set.insert(*action));
+        set
+    }
+
+    #[test]
+    fn binding_twice_keeps_one_binding() {
+        let mut map = ActionMap::new();
+        map.bind(key("KeyW"), Action::Flap);
+        map.bind(key("KeyW"), Action::Flap);
+
+        assert_eq!(map.bindings(Action::Flap).count(), 1);
+    }
+
+    #[test]
+    fn unbinding_leaves_other_actions_of_the_control() {
+        let mut map = ActionMap::default();
+        map.unbind(&key("Space"), Action::Flap);
+
+        assert!(!map.bindings(Action::Flap).any(|binding| *binding == key("Space")));
+        assert!(map.bindings(Action::Confirm).any(|binding| *binding == key("Space")));
+        assert!(map.is_bound(&key("Space")));
+    }
+
+    #[test]
+    fn rebinding_replaces_only_the_controls_of_that_action() {
+        let mut map = ActionMap::default();
+        map.rebind(Action::Flap, [key("KeyW"), Binding::Pointer]);
+
+        assert_eq!(map.bindings(Action::Flap).collect::<Vec<_>>(), [&key("KeyW"), &Binding::Pointer]);
+        assert!(map.bindings(Action::Confirm).any(|binding| *binding == key("Space")));
+        assert!(!map.is_bound(&key("ArrowUp")));
+    }
+
+    #[test]
+    fn presses_and_releases_are_edges_of_held() {
+        let first = InputState::new(held(&[Action::Flap]), &InputState::default(), Position::default());
+        assert!(first.is_pressed(Action::Flap) && first.is_held(Action::Flap));
+
+        let second = InputState::new(held(&[Action::Flap, Action::Left]), &first, Position::default());
+        assert!(!second.is_pressed(Action::Flap) && second.is_held(Action::Flap));
+        assert!(second.is_pressed(Action::Left));
+
+        let third = InputState::new(held(&[Action::Left]), &second, Position::default());
+        assert!(third.is_released(Action::Flap) && !third.is_held(Action::Flap));
+        assert!(!third.is_released(Action::Left));
+        assert!(!third.any_pressed(&[Action::Flap, Action::Left]));
+    }
+
+    #[test]
+    fn taps_shorter_than_a_tick_still_press() {
+        let mut devices = InputDevices::new(ActionMap::default());
+        devices.press(key("Space"));
+        devices.release(&key("Space"));
+
+        let tapped = devices.poll();
+        assert!(tapped.is_pressed(Action::Flap) && tapped.is_pressed(Action::Confirm));
+
+        let after = devices.poll();
+        assert!(after.is_released(Action::Flap) && !after.is_held(Action::Flap));
+    }
+
+    #[test]
+    fn rebound_controls_drive_their_new_action() {
+        let mut devices = InputDevices::new(ActionMap::default());
+        devices.map_mut().rebind(Action::Flap, [key("KeyW")]);
+
+        devices.press(key("ArrowUp"));
+        assert!(!devices.poll().is_held(Action::Flap));
+
+        devices.press(key("KeyW"));
+        assert!(devices.poll().is_pressed(Action::Flap));
+    }
+
+    #[test]
+    fn requests_last_a_single_tick() {
+        let mut devices = InputDevices::new(ActionMap::default());
+        devices.request(Action::Suspend);
+
+        assert!(devices.poll().is_pressed(Action::Suspend));
+        assert!(devices.poll().is_released(Action::Suspend));
+    }
+}
@@ -1,23 +1,106 @@
-use anyhow::Result;
-use engine::Engine;
-use game::{TappyPlane, Waiting};
+use std::{cell::RefCell, rc::Rc};
+
+use engine::{Engine, FixedTimestep};
+use input::{Action, ActionMap, Binding, InputDevices};
+use game::TappyPlane;
+use replay::Replay;
 use wasm_bindgen::prelude::*;
 
 #[macro_use]
-mod browser;
-mod physics;
-mod engine;
-mod utils;
-mod game;
+pub mod browser;
+pub mod physics;
+pub mod engine;
+pub mod input;
+pub mod font;
+pub mod utils;
+pub mod game;
+pub mod software;
+pub mod replay;
+pub mod scores;
+pub mod outline;
+pub mod config;
+pub mod difficulty;
+pub mod biome;
+pub mod particles;
+pub mod sprites;
+pub mod atlas;
+
+const TICK_RATE: f64 = 60.0;
+const MAX_CATCH_UP_STEPS: u32 = 5;
+
+thread_local! {
+    static RECORDING: RefCell<Option<Rc<RefCell<Replay>>>> = const { RefCell::new(None) };
+    static DEVICES: RefCell<Option<Rc<RefCell<InputDevices>>>> = const { RefCell::new(None) };
+}
 
 #[wasm_bindgen(start)]
 pub fn main_js() -> Result<(), JsValue> {
     console_error_panic_hook::set_once();
 
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn run(seed: u32) {
+    let recording = Rc::new(RefCell::new(Replay::new(seed.into())));
+    RECORDING.with(|current| *current.borrow_mut() = Some(recording.clone()));
+    let devices = Rc::new(RefCell::new(InputDevices::new(ActionMap::default())));
+    DEVICES.with(|current| *current.borrow_mut() = Some(devices.clone()));
+
     browser::spawn_local(async move {
-        let game = TappyPlane::default();
-        Engine::start(game).await.expect("Could not start game loop");
+        let game = TappyPlane::new(seed.into());
+        let timestep = FixedTimestep::new(TICK_RATE, MAX_CATCH_UP_STEPS);
+        Engine::start(game, timestep, recording, devices).await.expect("Could not start game loop");
     });
+}
 
-    Ok(())
+/// Serializes everything played since `run` so a session can be attached to a bug report.
+#[wasm_bindgen]
+pub fn export_replay() -> Result<String, JsValue> {
+    RECORDING.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .ok_or_else(|| JsValue::from_str("No game is running"))?
+            .borrow()
+            .to_json()
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+    })
+}
+
+fn with_devices<T>(f: impl FnOnce(&mut InputDevices) -> Result<T, JsValue>) -> Result<T, JsValue> {
+    DEVICES.with(|current| {
+        let current = current.borrow();
+        let devices = current.as_ref().ok_or_else(|| JsValue::from_str("No game is running"))?;
+        let mut devices = devices.borrow_mut();
+        f(&mut devices)
+    })
+}
+
+fn parse_action(action: &str) -> Result<Action, JsValue> {
+    serde_json::from_value(serde_json::Value::String(action.to_string()))
+        .map_err(|_| JsValue::from_str(&format!("{} is not an action", action)))
+}
+
+/// Controls bound to `action`, like `"Flap"`, as a JSON array such as
+/// `[{"Key":"Space"},"Pointer",{"GamepadButton":0}]`.
+#[wasm_bindgen]
+pub fn bindings(action: &str) -> Result<String, JsValue> {
+    let action = parse_action(action)?;
+    with_devices(|devices| {
+        let bindings: Vec<&Binding> = devices.map().bindings(action).collect();
+        serde_json::to_string(&bindings).map_err(|err| JsValue::from_str(&err.to_string()))
+    })
+}
+
+/// Replaces every control bound to `action` with `bindings`, in the JSON `bindings` returns.
+#[wasm_bindgen]
+pub fn rebind(action: &str, bindings: &str) -> Result<(), JsValue> {
+    let action = parse_action(action)?;
+    let bindings: Vec<Binding> = serde_json::from_str(bindings)
+        .map_err(|err| JsValue::from_str(&format!("Could not parse bindings: {}", err)))?;
+    with_devices(|devices| {
+        devices.map_mut().rebind(action, bindings);
+        Ok(())
+    })
 }
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use crate::{engine::Rect, software::Bitmap};
+
+/// Collider outlines keyed by atlas entry, vertices in pixels from the sprite's top-left corner.
+pub type ColliderOutlines = HashMap<String, Vec<[f32; 2]>>;
+
+/// Traces the opaque part of `rect` inside `bitmap` into a closed polygon.
+///
+/// Each column contributes its top-most and bottom-most pixel whose alpha reaches
+/// `alpha_threshold`, so gaps inside a column are filled in. The two chains are then
+/// simplified, dropping vertices that sit within `tolerance` pixels of the outline.
+pub fn trace(bitmap: &Bitmap, rect: &Rect, alpha_threshold: u8, tolerance: f64) -> Vec<[f32; 2]> {
+    let mut top_chain: Vec<[f64; 2]> = Vec::new();
+    let mut bottom_chain: Vec<[f64; 2]> = Vec::new();
+
+    for column in 0..rect.width {
+        let x = (rect.x + column) as u32;
+        let opaque = |row: &i32| bitmap.pixel(x, (rect.y + row) as u32)[3] >= alpha_threshold;
+
+        let Some(top) = (0..rect.height).find(opaque) else { continue };
+        let bottom = (0..rect.height).rev().find(opaque).unwrap_or(top);
+
+        top_chain.push([column as f64, top as f64]);
+        top_chain.push([(column + 1) as f64, top as f64]);
+        bottom_chain.push([column as f64, (bottom + 1) as f64]);
+        bottom_chain.push([(column + 1) as f64, (bottom + 1) as f64]);
+    }
+
+    bottom_chain.reverse();
+
+    simplify(&top_chain, tolerance)
+        .into_iter()
+        .chain(simplify(&bottom_chain, tolerance))
+        .map(|[x, y]| [x as f32, y as f32])
+        .collect()
+}
+
+/// Ramer-Douglas-Peucker over an open polyline, both end points are always kept.
+fn simplify(points: &[[f64; 2]], tolerance: f64) -> Vec<[f64; 2]> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let first = points[0];
+    let last = points[points.len() - 1];
+    let (index, distance) = points[1..points.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(index, point)| (index + 1, distance_to_segment(point, &first, &last)))
+        .fold((0, 0.0), |farthest, candidate| if candidate.1 > farthest.1 { candidate } else { farthest });
+
+    if distance <= tolerance {
+        return vec![first, last];
+    }
+
+    let mut simplified = simplify(&points[..=index], tolerance);
+    simplified.pop();
+    simplified.extend(simplify(&points[index..], tolerance));
+    simplified
+}
+
+fn distance_to_segment(point: &[f64; 2], start: &[f64; 2], end: &[f64; 2]) -> f64 {
+    let (dx, dy) = (end[0] - start[0], end[1] - start[1]);
+    let length_squared = dx * dx + dy * dy;
+    if length_squared == 0.0 {
+        return ((point[0] - start[0]).powi(2) + (point[1] - start[1]).powi(2)).sqrt();
+    }
+
+    let t = (((point[0] - start[0]) * dx + (point[1] - start[1]) * dy) / length_squared).clamp(0.0, 1.0);
+    let (x, y) = (start[0] + t * dx, start[1] + t * dy);
+    ((point[0] - x).powi(2) + (point[1] - y).powi(2)).sqrt()
+}
@@ -0,0 +1,240 @@
+use crate::{
+    engine::{Image, Position, Renderer, SpriteDraw},
+    sprites::{Sprite, Sprites},
+    utils::Rng,
+};
+
+/// Value picked uniformly between `min` and `max` for every new particle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spread {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Spread {
+    fn sample(&self, rng: &mut Rng) -> f64 {
+        self.min + rng.next_f64() * (self.max - self.min)
+    }
+}
+
+/// Linear ramp from `start` at birth to `end` at death.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Curve {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Curve {
+    pub fn at(&self, progress: f64) -> f64 {
+        self.start + (self.end - self.start) * progress.clamp(0.0, 1.0)
+    }
+}
+
+/// How one kind of particle looks and moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticleSettings {
+    pub sprite: Sprite,
+    /// Seconds before the particle disappears.
+    pub lifetime: Spread,
+    /// Launch speed in px/s.
+    pub speed: Spread,
+    /// Launch angle in radians, 0 points right and positive angles turn clockwise.
+    pub direction: Spread,
+    pub scale: Curve,
+    pub alpha: Curve,
+    /// Downwards acceleration in px/s².
+    pub gravity: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Particle {
+    pub position: Position,
+    pub velocity: Position,
+    pub age: f64,
+    pub lifetime: f64,
+    pub settings: ParticleSettings,
+}
+
+impl Particle {
+    pub fn progress(&self) -> f64 {
+        self.age / self.lifetime
+    }
+}
+
+/// Fixed-size pool of particles. Emitting into a full pool drops the new particles,
+/// so the pool never allocates after `new`. All randomness comes from its own seeded
+/// generator and time only moves through `update`, so a run always plays out the same.
+#[derive(Debug, Clone)]
+pub struct Emitter {
+    slots: Vec<Option<Particle>>,
+    rng: Rng,
+}
+
+impl Emitter {
+    pub fn new(capacity: usize, seed: u64) -> Self {
+        Emitter { slots: vec![None; capacity], rng: Rng::new(seed) }
+    }
+
+    /// Launches up to `count` particles from `origin`. `carried` is added to every launch
+    /// velocity, for particles that should drift along with what emitted them.
+    pub fn emit(&mut self, settings: &ParticleSettings, origin: &Position, carried: &Position, count: usize) {
+        let free_slots = self.slots.iter_mut().filter(|slot| slot.is_none()).take(count);
+
+        for slot in free_slots {
+            let speed = settings.speed.sample(&mut self.rng);
+            let direction = settings.direction.sample(&mut self.rng);
+            let lifetime = settings.lifetime.sample(&mut self.rng).max(f64::EPSILON);
+
+            *slot = Some(Particle {
+                position: *origin,
+                velocity: Position {
+                    x: carried.x + direction.cos() * speed,
+                    y: carried.y + direction.sin() * speed,
+                },
+                age: 0.0,
+                lifetime,
+                settings: *settings,
+            });
+        }
+    }
+
+    pub fn update(&mut self, delta: &f64) {
+        for slot in self.slots.iter_mut() {
+            let Some(particle) = slot else { continue };
+
+            particle.age += delta;
+            if particle.age >= particle.lifetime {
+                *slot = None;
+                continue;
+            }
+
+            particle.velocity.y += particle.settings.gravity * delta;
+            particle.position.x += particle.velocity.x * delta;
+            particle.position.y += particle.velocity.y * delta;
+        }
+    }
+
+    pub fn particles(&self) -> impl Iterator<Item = &Particle> {
+        self.slots.iter().flatten()
+    }
+
+    /// Draws every live particle with its sprite's pivot, or middle, on its position.
+    /// `offset` shifts them horizontally.
+    pub fn draw(&self, offset: f64, renderer: &Renderer, images: &[Image], sprites: &Sprites) {
+        for particle in self.particles() {
+            let sprite = &sprites[particle.settings.sprite];
+            let progress = particle.progress();
+            let scale = particle.settings.scale.at(progress);
+
+            renderer.draw_sheet_sprite(
+                images,
+                sprite,
+                &SpriteDraw {
+                    scale: Position { x: scale, y: scale },
+                    alpha: particle.settings.alpha.at(progress),
+                    ..SpriteDraw::pivoted(sprite, particle.position.x + offset, particle.position.y)
+                }
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUFF: ParticleSettings = ParticleSettings {
+        sprite: Sprite::PuffSmall,
+        lifetime: Spread { min: 0.5, max: 1.0 },
+        speed: Spread { min: 20.0, max: 80.0 },
+        direction: Spread { min: 0.0, max: 6.0 },
+        scale: Curve { start: 1.0, end: 0.5 },
+        alpha: Curve { start: 1.0, end: 0.0 },
+        gravity: 50.0,
+    };
+
+    /// Launches straight right at 10 px/s and lives for exactly `lifetime` seconds.
+    fn steady(lifetime: f64) -> ParticleSettings {
+        ParticleSettings {
+            lifetime: Spread { min: lifetime, max: lifetime },
+            speed: Spread { min: 10.0, max: 10.0 },
+            direction: Spread { min: 0.0, max: 0.0 },
+            gravity: 0.0,
+            ..PUFF
+        }
+    }
+
+    #[test]
+    fn emitted_particles_move_with_their_launch_and_carried_velocity() {
+        let mut emitter = Emitter::new(4, 1);
+        emitter.emit(&steady(1.0), &Position { x: 100.0, y: 50.0 }, &Position { x: -4.0, y: 2.0 }, 1);
+
+        emitter.update(&0.5);
+
+        let particles: Vec<&Particle> = emitter.particles().collect();
+        assert_eq!(particles.len(), 1);
+        assert_eq!(particles[0].velocity, Position { x: 6.0, y: 2.0 });
+        assert_eq!(particles[0].position, Position { x: 103.0, y: 51.0 });
+        assert_eq!(particles[0].progress(), 0.5);
+    }
+
+    #[test]
+    fn gravity_pulls_particles_down() {
+        let mut emitter = Emitter::new(1, 1);
+        emitter.emit(&ParticleSettings { gravity: 40.0, ..steady(1.0) }, &Position::default(), &Position::default(), 1);
+
+        emitter.update(&0.25);
+
+        let particle = emitter.particles().next().unwrap();
+        assert_eq!(particle.velocity.y, 10.0);
+        assert_eq!(particle.position.y, 2.5);
+    }
+
+    #[test]
+    fn particles_expire_at_the_end_of_their_lifetime() {
+        let mut emitter = Emitter::new(4, 1);
+        emitter.emit(&steady(0.5), &Position::default(), &Position::default(), 2);
+        emitter.emit(&steady(1.0), &Position::default(), &Position::default(), 1);
+
+        emitter.update(&0.25);
+        assert_eq!(emitter.particles().count(), 3);
+
+        emitter.update(&0.25);
+        assert_eq!(emitter.particles().count(), 1);
+
+        emitter.update(&0.5);
+        assert_eq!(emitter.particles().count(), 0);
+    }
+
+    #[test]
+    fn full_pools_drop_new_particles_and_reuse_expired_slots() {
+        let mut emitter = Emitter::new(3, 1);
+        emitter.emit(&steady(0.5), &Position::default(), &Position::default(), 2);
+        emitter.emit(&steady(1.0), &Position { x: 10.0, y: 0.0 }, &Position::default(), 5);
+        assert_eq!(emitter.particles().count(), 3);
+
+        emitter.update(&0.5);
+        emitter.emit(&steady(1.0), &Position { x: 20.0, y: 0.0 }, &Position::default(), 5);
+
+        let origins: Vec<f64> = emitter.particles().map(|particle| particle.position.x).collect();
+        assert_eq!(emitter.slots.len(), 3);
+        assert_eq!(origins, [20.0, 20.0, 15.0]);
+    }
+
+    #[test]
+    fn same_seed_emits_the_same_particles() {
+        let burst = |seed: u64| {
+            let mut emitter = Emitter::new(16, seed);
+            emitter.emit(&PUFF, &Position { x: 40.0, y: 40.0 }, &Position::default(), 8);
+            for _ in 0..30 {
+                emitter.update(&(1.0 / 60.0));
+            }
+            emitter.emit(&PUFF, &Position { x: 80.0, y: 40.0 }, &Position::default(), 8);
+            emitter.particles().copied().collect::<Vec<Particle>>()
+        };
+
+        assert_eq!(burst(3).len(), 16);
+        assert_eq!(burst(3), burst(3));
+        assert_ne!(burst(3), burst(4));
+    }
+}
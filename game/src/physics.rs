@@ -1,6 +1,4 @@
-use std::ops::Index;
-
-use rapier2d::{prelude::*, na::{Vector2, Point, Point2}};
+use rapier2d::{prelude::*, na::{Vector2, Point2}, crossbeam::channel::{self, Receiver}};
 
 use crate::engine::{Rect, Position};
 
@@ -11,17 +9,22 @@ pub struct World {
     integration_parameters: IntegrationParameters,
     pipeline: PhysicsPipeline,
     island_manager: IslandManager,
-    broad_phase: BroadPhase,
+    broad_phase: DefaultBroadPhase,
     narrow_phase: NarrowPhase,
     impulse_joint_set: ImpulseJointSet,
     multibody_joint_set: MultibodyJointSet,
     ccd_solver: CCDSolver,
     physics_hooks: Box<dyn PhysicsHooks>,
-    event_handler: Box<dyn EventHandler>
+    event_handler: Box<dyn EventHandler>,
+    collision_events: Receiver<CollisionEvent>,
 }
 
 impl Default for World {
     fn default() -> Self {
+        let (collision_send, collision_events) = channel::unbounded();
+        // Contact forces are never requested, nothing reads that side of the collector
+        let (contact_force_send, _) = channel::unbounded();
+
         World { 
             gravity: Vector2::new(0.0, 9.81 * 10.0 * 2.0), 
             rigid_body_set: RigidBodySet::new(),
@@ -29,19 +32,28 @@ impl Default for World {
             integration_parameters: IntegrationParameters::default(),
             pipeline: PhysicsPipeline::new(),
             island_manager: IslandManager::new(),
-            broad_phase: BroadPhase::new(), 
+            broad_phase: DefaultBroadPhase::new(), 
             narrow_phase: NarrowPhase::new(),
             impulse_joint_set: ImpulseJointSet::new(),
             multibody_joint_set: MultibodyJointSet::new(),
             ccd_solver: CCDSolver::new(),
             physics_hooks: Box::new(()),
-            event_handler: Box::new(())
+            event_handler: Box::new(ChannelEventCollector::new(collision_send, contact_force_send)),
+            collision_events,
         }
     }
 }
 
 impl World {
-    pub fn update(&mut self) {
+    pub fn new(gravity: f64) -> Self {
+        World { 
+            gravity: Vector2::new(0.0, gravity as Real), 
+            ..World::default()
+        }
+    }
+
+    pub fn update(&mut self, delta: f64) {
+        self.integration_parameters.dt = delta as Real;
         self.pipeline.step(
             &self.gravity, 
             &self.integration_parameters, 
@@ -73,7 +85,9 @@ impl World {
         self.collider_set.insert(collider)
     }
 
-    pub fn add_plane(&mut self, rect: &Rect) -> RigidBodyHandle {
+    /// Adds the plane, shaped after `outline` when the sprite was traced and as a box otherwise.
+    /// Contacts never spin it, its angle only changes through `set_rotation`.
+    pub fn add_plane(&mut self, rect: &Rect, outline: Option<&[[f32; 2]]>) -> RigidBodyHandle {
         let rigid_body = RigidBodyBuilder::dynamic()
             .translation(
                 vector![
@@ -85,10 +99,12 @@ impl World {
             .lock_rotations()
             .build();
 
-        let collider = ColliderBuilder::cuboid(
-            (rect.width as f32)/2.0, (rect.height as f32)/2.0
-        )
-            // .active_events(ActiveEvents::COLLISION_EVENTS)
+        let collider = outline
+            .map(|outline| outline_collider(outline, rect))
+            .unwrap_or_else(|| ColliderBuilder::cuboid((rect.width as f32)/2.0, (rect.height as f32)/2.0))
+            // Same mass as the full box whatever the shape, flap impulses are tuned for it
+            .mass((rect.width * rect.height) as Real)
+            .active_events(ActiveEvents::COLLISION_EVENTS)
             .restitution(0.0)
             .build();
         let body_handle = self.rigid_body_set.insert(rigid_body);
@@ -96,34 +112,126 @@ impl World {
         body_handle
     }
 
-    pub fn add_obstacle(&mut self, rect: &Rect) -> RigidBodyHandle{
-        let rigid_body = RigidBodyBuilder::kinematic_position_based()
+    /// Adds a rock that slides left at `speed` px/s. Without a traced `outline` it falls
+    /// back to a triangle: `pointing_up` rocks grow from the ground with their tip at the
+    /// top, the others hang from the ceiling.
+    pub fn add_obstacle(&mut self, rect: &Rect, pointing_up: bool, speed: f64, outline: Option<&[[f32; 2]]>) -> RigidBodyHandle {
+        let half_width = rect.width as f32 / 2.0;
+        let half_height = rect.height as f32 / 2.0;
+
+        let rigid_body = RigidBodyBuilder::kinematic_velocity_based()
+            .translation(
+                vector![
+                    rect.x as f32 + half_width, 
+                    rect.y as f32 + half_height
+                ]
+            )
+            .linvel(vector![-speed as f32, 0.0])
+            .build();
+
+        let (base, tip) = if pointing_up { (half_height, -half_height) } else { (-half_height, half_height) };
+        let collider = outline
+            .map(|outline| outline_collider(outline, rect))
+            .unwrap_or_else(|| ColliderBuilder::triangle(
+                Point2::new(-half_width, base), 
+                Point2::new(0.0, tip), 
+                Point2::new(half_width, base)
+            ))
+            .restitution(0.0)
+            .build();
+        let handle = self.rigid_body_set.insert(rigid_body);
+        self.collider_set.insert_with_parent(collider, handle, &mut self.rigid_body_set);
+        handle
+    }
+
+    /// Pushes the body with `acceleration` px/s² downwards for `delta` seconds.
+    pub fn accelerate(&mut self, handle: &RigidBodyHandle, acceleration: f64, delta: f64) {
+        if let Some(body) = self.rigid_body_set.get_mut(*handle) {
+            let impulse = body.mass() as f64 * acceleration * delta;
+            body.apply_impulse(vector![0.0, impulse as f32], true);
+        }
+    }
+
+    /// Keeps the horizontal speed and replaces the vertical one, in px/s.
+    pub fn set_vertical_velocity(&mut self, handle: &RigidBodyHandle, velocity: f64) {
+        if let Some(body) = self.rigid_body_set.get_mut(*handle) {
+            let horizontal = body.linvel().x;
+            body.set_linvel(vector![horizontal, velocity as Real], true);
+        }
+    }
+
+    /// Turns the body to `angle` radians, clockwise, carrying its colliders with it.
+    pub fn set_rotation(&mut self, handle: &RigidBodyHandle, angle: f64) {
+        if let Some(body) = self.rigid_body_set.get_mut(*handle) {
+            body.set_rotation(Rotation::new(angle as Real), true);
+        }
+    }
+
+    pub fn set_drag(&mut self, handle: &RigidBodyHandle, drag: f64) {
+        if let Some(body) = self.rigid_body_set.get_mut(*handle) {
+            body.set_linear_damping(drag as Real);
+        }
+    }
+
+    /// Adds a star the plane can fly through to pick it up, sliding left at `speed` px/s.
+    pub fn add_star(&mut self, rect: &Rect, speed: f64) -> RigidBodyHandle {
+        let rigid_body = RigidBodyBuilder::kinematic_velocity_based()
             .translation(
                 vector![
                     (rect.x + rect.width/2) as f32, 
                     (rect.y + rect.height/2) as f32
                 ]
             )
-            .locked_axes(LockedAxes::TRANSLATION_LOCKED_Y)
-            .lock_rotations()
+            .linvel(vector![-speed as f32, 0.0])
             .build();
-        let collider = ColliderBuilder::triangle(
-                Point2::new(0.0, 0.0), 
-                Point2::new((rect.width/2) as f32, rect.height as f32), 
-                Point2::new(rect.width as f32, 0.0)
-            )
-            .restitution(0.0)
+
+        let collider = ColliderBuilder::ball(rect.width.min(rect.height) as f32 / 2.0)
+            .sensor(true)
+            .active_events(ActiveEvents::COLLISION_EVENTS)
             .build();
         let handle = self.rigid_body_set.insert(rigid_body);
         self.collider_set.insert_with_parent(collider, handle, &mut self.rigid_body_set);
         handle
     }
 
+    /// Sets how fast a rock or star slides left, in px/s.
+    pub fn set_scroll_speed(&mut self, handle: &RigidBodyHandle, speed: f64) {
+        if let Some(body) = self.rigid_body_set.get_mut(*handle) {
+            body.set_linvel(vector![-speed as f32, 0.0], true);
+        }
+    }
+
+    pub fn remove_body(&mut self, handle: &RigidBodyHandle) {
+        self.rigid_body_set.remove(
+            *handle, 
+            &mut self.island_manager, 
+            &mut self.collider_set, 
+            &mut self.impulse_joint_set, 
+            &mut self.multibody_joint_set, 
+            true
+        );
+    }
+
+    /// Drains the collision events raised since the last call.
+    pub fn collision_events(&self) -> Vec<CollisionEvent> {
+        self.collision_events.try_iter().collect()
+    }
+
+    pub fn collider_parent(&self, handle: &ColliderHandle) -> Option<RigidBodyHandle> {
+        self.collider_set.get(*handle).and_then(|collider| collider.parent())
+    }
+
     pub fn get_body_position(&self, handle: &RigidBodyHandle) -> Position {
         let body = &self.rigid_body_set[*handle];
         Position { x: body.translation().x as f64, y: body.translation().y as f64 }
     }
 
+    /// Velocity in px/s, positive y is down.
+    pub fn get_body_velocity(&self, handle: &RigidBodyHandle) -> Position {
+        let body = &self.rigid_body_set[*handle];
+        Position { x: body.linvel().x as f64, y: body.linvel().y as f64 }
+    }
+
     pub fn add_impulse(&mut self, handle: &RigidBodyHandle, impulse: f32) {
         self
             .rigid_body_set
@@ -132,3 +240,19 @@ impl World {
             .apply_impulse(vector![0.0, impulse], true);
     }
 }
+
+/// Convex decomposition of a traced outline, recentred on the middle of `rect`
+/// like every body translation in this world.
+fn outline_collider(outline: &[[f32; 2]], rect: &Rect) -> ColliderBuilder {
+    let half_width = rect.width as f32 / 2.0;
+    let half_height = rect.height as f32 / 2.0;
+
+    let vertices: Vec<Point2<Real>> = outline
+        .iter()
+        .map(|[x, y]| Point2::new(x - half_width, y - half_height))
+        .collect();
+    let count = vertices.len() as u32;
+    let indices: Vec<[u32; 2]> = (0..count).map(|index| [index, (index + 1) % count]).collect();
+
+    ColliderBuilder::convex_decomposition(&vertices, &indices)
+}
@@ -0,0 +1,73 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::GameConfig, engine::Game, input::InputState, outline::ColliderOutlines, scores::ScoreEntry};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Frame {
+    pub delta: f64,
+    pub input: InputState,
+}
+
+/// What a session was built from besides its seed. The browser loads these from files
+/// and localStorage, so a replay carries its own copy rather than trusting the player's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionStart {
+    pub config: GameConfig,
+    pub colliders: ColliderOutlines,
+    /// High scores when the session started, which decide the skins the hangar lets through.
+    pub scores: Vec<ScoreEntry>,
+}
+
+/// Every tick's delta and input for one session, enough to rebuild it exactly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    /// `None` until the game has loaded.
+    pub start: Option<SessionStart>,
+    pub frames: Vec<Frame>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Replay { seed, start: None, frames: Vec::new() }
+    }
+
+    /// Keeps what the loaded game starts from, dropping any input recorded before.
+    pub fn begin(&mut self, start: SessionStart) {
+        self.start = Some(start);
+        self.frames.clear();
+    }
+
+    pub fn record(&mut self, delta: f64, input: InputState) {
+        self.frames.push(Frame { delta, input });
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(|err| anyhow!("Could not serialize replay: {}", err))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|err| anyhow!("Could not parse replay: {}", err))
+    }
+
+    /// Rebuilds the recorded session in `game` and feeds the recording back through
+    /// `Game::update`, no browser involved.
+    pub fn play(&self, game: &mut dyn Game) -> Result<()> {
+        if game.seed() != self.seed {
+            return Err(
+                anyhow!("Replay was recorded with seed {} but the game uses seed {}", self.seed, game.seed())
+            );
+        }
+        let start = self.start
+            .as_ref()
+            .ok_or_else(|| anyhow!("Replay was recorded before the game loaded"))?;
+
+        game.restore(start)?;
+        for frame in self.frames.iter() {
+            game.update(&frame.delta, &frame.input);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    /// Milliseconds since the Unix epoch.
+    pub date: f64,
+    pub score: u32,
+    pub plane_color: String,
+    /// Seed of the run's course.
+    pub seed: u64,
+    /// Value of the stars picked up during the run, tables saved before stars existed have none.
+    #[serde(default)]
+    pub stars: u32,
+}
+
+pub trait ScoreStore {
+    fn load(&self) -> Result<Vec<ScoreEntry>>;
+    fn save(&mut self, entries: &[ScoreEntry]) -> Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: Vec<ScoreEntry>,
+}
+
+impl MemoryStore {
+    pub fn new(entries: Vec<ScoreEntry>) -> Self {
+        MemoryStore { entries }
+    }
+}
+
+impl ScoreStore for MemoryStore {
+    fn load(&self) -> Result<Vec<ScoreEntry>> {
+        Ok(self.entries.clone())
+    }
+
+    fn save(&mut self, entries: &[ScoreEntry]) -> Result<()> {
+        self.entries = entries.to_vec();
+        Ok(())
+    }
+}
+
+/// Keeps the table in a JSON file, for native tools and tests.
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileStore { path: path.into() }
+    }
+}
+
+impl ScoreStore for FileStore {
+    fn load(&self) -> Result<Vec<ScoreEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let json = fs::read_to_string(&self.path)
+            .map_err(|err| anyhow!("Could not read {}: {}", self.path.display(), err))?;
+        serde_json::from_str(&json)
+            .map_err(|err| anyhow!("Could not parse {}: {}", self.path.display(), err))
+    }
+
+    fn save(&mut self, entries: &[ScoreEntry]) -> Result<()> {
+        let json = serde_json::to_string_pretty(entries)
+            .map_err(|err| anyhow!("Could not serialize high scores: {}", err))?;
+        fs::write(&self.path, json)
+            .map_err(|err| anyhow!("Could not write {}: {}", self.path.display(), err))
+    }
+}
+
+/// Best `capacity` runs, highest score first.
+pub struct HighScores {
+    store: Box<dyn ScoreStore>,
+    entries: Vec<ScoreEntry>,
+    capacity: usize,
+}
+
+impl HighScores {
+    pub fn load(store: Box<dyn ScoreStore>, capacity: usize) -> Result<Self> {
+        let entries = store.load()?;
+        Ok(HighScores::with_entries(store, entries, capacity))
+    }
+
+    /// Like `load`, but starts an empty table over the same store when it cannot be read,
+    /// handing back why. The next save overwrites whatever the store held.
+    pub fn load_or_empty(store: Box<dyn ScoreStore>, capacity: usize) -> (Self, Option<anyhow::Error>) {
+        match store.load() {
+            Ok(entries) => (HighScores::with_entries(store, entries, capacity), None),
+            Err(err) => (HighScores::with_entries(store, Vec::new(), capacity), Some(err)),
+        }
+    }
+
+    fn with_entries(store: Box<dyn ScoreStore>, mut entries: Vec<ScoreEntry>, capacity: usize) -> Self {
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        entries.truncate(capacity);
+
+        HighScores { store, entries, capacity }
+    }
+
+    pub fn entries(&self) -> &[ScoreEntry] {
+        &self.entries
+    }
+
+    pub fn best(&self) -> Option<u32> {
+        self.entries.first().map(|entry| entry.score)
+    }
+
+    /// Adds `entry` to the table and returns its 1-based rank, or `None` when it did
+    /// not make the cut. Ties go below the runs that got there first.
+    pub fn submit(&mut self, entry: ScoreEntry) -> Option<usize> {
+        let index = self.entries
+            .iter()
+            .position(|existing| existing.score < entry.score)
+            .unwrap_or(self.entries.len());
+
+        if index >= self.capacity {
+            return None;
+        }
+
+        self.entries.insert(index, entry);
+        self.entries.truncate(self.capacity);
+
+        Some(index + 1)
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        self.store.save(&self.entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(score: u32) -> ScoreEntry {
+        ScoreEntry { date: 0.0, score, plane_color: String::from("Red"), seed: 1, stars: 0 }
+    }
+
+    #[test]
+    fn unreadable_tables_start_empty_and_get_overwritten() {
+        let path = std::env::temp_dir().join(format!("tappy-plane-scores-{}.json", std::process::id()));
+        fs::write(&path, "{ not a score table").unwrap();
+
+        let (mut scores, err) = HighScores::load_or_empty(Box::new(FileStore::new(&path)), 10);
+        assert!(err.is_some());
+        assert!(scores.entries().is_empty());
+
+        assert_eq!(scores.submit(run(12)), Some(1));
+        scores.save().unwrap();
+        let reloaded = HighScores::load(Box::new(FileStore::new(&path)), 10).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(reloaded.entries(), [run(12)]);
+    }
+
+    #[test]
+    fn readable_tables_load_sorted_and_trimmed() {
+        let store = MemoryStore::new(vec![run(3), run(9), run(5)]);
+
+        let (scores, err) = HighScores::load_or_empty(Box::new(store), 2);
+
+        assert!(err.is_none());
+        assert_eq!(scores.entries(), [run(9), run(5)]);
+    }
+}
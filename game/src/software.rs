@@ -0,0 +1,271 @@
+use std::cell::{Cell, RefCell};
+
+use anyhow::{anyhow, Result};
+
+use crate::engine::{Image, Rect, RenderBackend};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Bitmap {
+    pub fn new(width: u32, height: u32) -> Self {
+        Bitmap { width, height, data: vec![0; (width * height * 4) as usize] }
+    }
+
+    pub fn from_rgba(width: u32, height: u32, data: Vec<u8>) -> Result<Self> {
+        if data.len() != (width * height * 4) as usize {
+            return Err(
+                anyhow!("Expected {} bytes for a {}x{} bitmap, got {}", width * height * 4, width, height, data.len())
+            );
+        }
+
+        Ok(Bitmap { width, height, data })
+    }
+
+    /// Decodes a PNG, converting whatever colour layout it uses to RGBA.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_png(bytes: &[u8]) -> Result<Self> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder
+            .read_info()
+            .map_err(|err| anyhow!("Could not read PNG header: {}", err))?;
+
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut buffer)
+            .map_err(|err| anyhow!("Could not decode PNG: {}", err))?;
+        buffer.truncate(info.buffer_size());
+
+        let data = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+                .collect(),
+            png::ColorType::Grayscale => buffer
+                .iter()
+                .flat_map(|&g| [g, g, g, 255])
+                .collect(),
+            png::ColorType::Indexed => return Err(anyhow!("Indexed PNG was not expanded")),
+        };
+
+        Bitmap::from_rgba(info.width, info.height, data)
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let index = ((y * self.width + x) * 4) as usize;
+        [self.data[index], self.data[index + 1], self.data[index + 2], self.data[index + 3]]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        let index = ((y * self.width + x) * 4) as usize;
+        self.data[index..index + 4].copy_from_slice(&color);
+    }
+
+    fn blend_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        let src_alpha = color[3] as f64 / 255.0;
+        if src_alpha <= 0.0 {
+            return;
+        }
+
+        let dest = self.pixel(x, y);
+        let dest_alpha = dest[3] as f64 / 255.0;
+        let out_alpha = src_alpha + dest_alpha * (1.0 - src_alpha);
+
+        let channel = |src: u8, dest: u8| {
+            let value = (src as f64 * src_alpha + dest as f64 * dest_alpha * (1.0 - src_alpha)) / out_alpha;
+            value.round() as u8
+        };
+
+        self.set_pixel(
+            x,
+            y,
+            [
+                channel(color[0], dest[0]),
+                channel(color[1], dest[1]),
+                channel(color[2], dest[2]),
+                (out_alpha * 255.0).round() as u8,
+            ]
+        );
+    }
+}
+
+/// Affine transform laid out like the canvas one: `x' = a*x + c*y + e`, `y' = b*x + d*y + f`.
+#[derive(Debug, Clone, Copy)]
+struct Transform {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+    f: f64,
+}
+
+impl Transform {
+    const IDENTITY: Transform = Transform { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.a * x + self.c * y + self.e, self.b * x + self.d * y + self.f)
+    }
+
+    fn invert(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let determinant = self.a * self.d - self.b * self.c;
+        if determinant.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let (x, y) = (x - self.e, y - self.f);
+        Some(((self.d * x - self.c * y) / determinant, (self.a * y - self.b * x) / determinant))
+    }
+}
+
+/// In-memory rasterizer so frames can be drawn without a browser.
+pub struct SoftwareBackend {
+    target: RefCell<Bitmap>,
+    transform: Cell<Transform>,
+    global_alpha: Cell<f64>,
+    stack: RefCell<Vec<(Transform, f64)>>,
+}
+
+impl SoftwareBackend {
+    pub fn new(width: u32, height: u32) -> Self {
+        SoftwareBackend {
+            target: RefCell::new(Bitmap::new(width, height)),
+            transform: Cell::new(Transform::IDENTITY),
+            global_alpha: Cell::new(1.0),
+            stack: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        self.target.borrow().pixel(x, y)
+    }
+
+    pub fn frame(&self) -> Bitmap {
+        self.target.borrow().clone()
+    }
+
+    /// Visits every target pixel whose centre lands inside `dest` once mapped back
+    /// through the current transform, handing over the position inside `dest`.
+    fn for_each_covered_pixel(&self, dest: &Rect, mut visit: impl FnMut(&mut Bitmap, u32, u32, f64, f64)) {
+        let transform = self.transform.get();
+        let mut target = self.target.borrow_mut();
+
+        let corners = [
+            transform.apply(dest.x as f64, dest.y as f64),
+            transform.apply((dest.x + dest.width) as f64, dest.y as f64),
+            transform.apply(dest.x as f64, (dest.y + dest.height) as f64),
+            transform.apply((dest.x + dest.width) as f64, (dest.y + dest.height) as f64),
+        ];
+        let min_x = corners.iter().map(|c| c.0).fold(f64::INFINITY, f64::min).floor().max(0.0) as u32;
+        let min_y = corners.iter().map(|c| c.1).fold(f64::INFINITY, f64::min).floor().max(0.0) as u32;
+        let max_x = corners.iter().map(|c| c.0).fold(f64::NEG_INFINITY, f64::max).ceil().min(target.width as f64) as u32;
+        let max_y = corners.iter().map(|c| c.1).fold(f64::NEG_INFINITY, f64::max).ceil().min(target.height as f64) as u32;
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let Some((u, v)) = transform.invert(x as f64 + 0.5, y as f64 + 0.5) else { return };
+                let (u, v) = (u - dest.x as f64, v - dest.y as f64);
+
+                if u >= 0.0 && v >= 0.0 && u < dest.width as f64 && v < dest.height as f64 {
+                    visit(&mut target, x, y, u, v);
+                }
+            }
+        }
+    }
+}
+
+impl RenderBackend for SoftwareBackend {
+    fn clear(&self, rect: &Rect) {
+        self.for_each_covered_pixel(rect, |target, x, y, _, _| target.set_pixel(x, y, [0; 4]));
+    }
+
+    fn draw_sub_image(&self, image: &Image, orig: &Rect, dest: &Rect) {
+        self.draw_tinted_sub_image(image, orig, dest, [255; 3]);
+    }
+
+    fn draw_tinted_sub_image(&self, image: &Image, orig: &Rect, dest: &Rect, tint: [u8; 3]) {
+        let Image::Bitmap(source) = image else { return };
+        if dest.width <= 0 || dest.height <= 0 {
+            return;
+        }
+
+        let scale_x = orig.width as f64 / dest.width as f64;
+        let scale_y = orig.height as f64 / dest.height as f64;
+        let global_alpha = self.global_alpha.get();
+
+        self.for_each_covered_pixel(dest, |target, x, y, u, v| {
+            let src_x = orig.x + (u * scale_x) as i32;
+            let src_y = orig.y + (v * scale_y) as i32;
+
+            if src_x >= 0 && src_y >= 0 && (src_x as u32) < source.width && (src_y as u32) < source.height {
+                let mut color = source.pixel(src_x as u32, src_y as u32);
+                for (channel, tint) in color.iter_mut().zip(tint) {
+                    *channel = (*channel as u32 * tint as u32 / 255) as u8;
+                }
+                color[3] = (color[3] as f64 * global_alpha).round() as u8;
+                target.blend_pixel(x, y, color);
+            }
+        });
+    }
+
+    fn save(&self) {
+        self.stack.borrow_mut().push((self.transform.get(), self.global_alpha.get()));
+    }
+
+    fn restore(&self) {
+        if let Some((transform, global_alpha)) = self.stack.borrow_mut().pop() {
+            self.transform.set(transform);
+            self.global_alpha.set(global_alpha);
+        }
+    }
+
+    fn translate(&self, x: f64, y: f64) {
+        let mut t = self.transform.get();
+        t.e += t.a * x + t.c * y;
+        t.f += t.b * x + t.d * y;
+        self.transform.set(t);
+    }
+
+    fn rotate(&self, angle: f64) {
+        let t = self.transform.get();
+        let (sin, cos) = angle.sin_cos();
+        self.transform.set(Transform {
+            a: t.a * cos + t.c * sin,
+            b: t.b * cos + t.d * sin,
+            c: t.c * cos - t.a * sin,
+            d: t.d * cos - t.b * sin,
+            e: t.e,
+            f: t.f,
+        });
+    }
+
+    fn scale(&self, x: f64, y: f64) {
+        let t = self.transform.get();
+        self.transform.set(Transform { a: t.a * x, b: t.b * x, c: t.c * y, d: t.d * y, ..t });
+    }
+
+    fn set_global_alpha(&self, alpha: f64) {
+        // Like the canvas, values outside [0, 1] are ignored
+        if (0.0..=1.0).contains(&alpha) {
+            self.global_alpha.set(alpha);
+        }
+    }
+
+    fn global_alpha(&self) -> f64 {
+        self.global_alpha.get()
+    }
+
+    fn create_image(&self, bitmap: &Bitmap) -> Result<Image> {
+        Ok(Image::Bitmap(bitmap.clone()))
+    }
+}
@@ -0,0 +1,309 @@
+use std::ops::Index;
+
+use anyhow::{anyhow, Result};
+
+use crate::{config::{GameConfig, Medal}, engine::{Clip, ClipFrame, SheetSprite, Spritesheet}, outline::ColliderOutlines};
+
+/// Seconds each of a skin's three `frames` stays up when it has no animation.
+const PROPELLER_FRAME_DURATION: f64 = 1.0 / 20.0;
+
+/// Sprites the game draws whatever the config says.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sprite {
+    Background,
+    TapLeft,
+    TapRight,
+    TextGetReady,
+    TextGameOver,
+    Panel,
+    PuffSmall,
+    PuffLarge,
+    StarBronze,
+    StarSilver,
+    StarGold,
+    MedalBronze,
+    MedalSilver,
+    MedalGold,
+    Number1,
+    Number2,
+    Number3,
+}
+
+impl Sprite {
+    const ALL: [Sprite; 17] = [
+        Sprite::Background,
+        Sprite::TapLeft,
+        Sprite::TapRight,
+        Sprite::TextGetReady,
+        Sprite::TextGameOver,
+        Sprite::Panel,
+        Sprite::PuffSmall,
+        Sprite::PuffLarge,
+        Sprite::StarBronze,
+        Sprite::StarSilver,
+        Sprite::StarGold,
+        Sprite::MedalBronze,
+        Sprite::MedalSilver,
+        Sprite::MedalGold,
+        Sprite::Number1,
+        Sprite::Number2,
+        Sprite::Number3,
+    ];
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Sprite::Background => "background.png",
+            Sprite::TapLeft => "tapLeft.png",
+            Sprite::TapRight => "tapRight.png",
+            Sprite::TextGetReady => "textGetReady.png",
+            Sprite::TextGameOver => "textGameOver.png",
+            Sprite::Panel => "UIbg.png",
+            Sprite::PuffSmall => "puffSmall.png",
+            Sprite::PuffLarge => "puffLarge.png",
+            Sprite::StarBronze => "starBronze.png",
+            Sprite::StarSilver => "starSilver.png",
+            Sprite::StarGold => "starGold.png",
+            Sprite::MedalBronze => "medalBronze.png",
+            Sprite::MedalSilver => "medalSilver.png",
+            Sprite::MedalGold => "medalGold.png",
+            Sprite::Number1 => "number1.png",
+            Sprite::Number2 => "number2.png",
+            Sprite::Number3 => "number3.png",
+        }
+    }
+
+    pub fn medal(medal: Medal) -> Sprite {
+        match medal {
+            Medal::Bronze => Sprite::MedalBronze,
+            Medal::Silver => Sprite::MedalSilver,
+            Medal::Gold => Sprite::MedalGold,
+        }
+    }
+
+    /// Digit of a 3, 2, 1 countdown, anything outside that range is clamped into it.
+    pub fn countdown(number: u32) -> Sprite {
+        match number {
+            0 | 1 => Sprite::Number1,
+            2 => Sprite::Number2,
+            _ => Sprite::Number3,
+        }
+    }
+}
+
+/// Characters the sprite font has glyphs for. Lower case is drawn with the upper case glyphs.
+pub fn font_characters() -> impl Iterator<Item = char> {
+    ('A'..='Z').chain('0'..='9')
+}
+
+/// Atlas entry of the glyph for `character`.
+pub fn glyph_name(character: char) -> String {
+    if character.is_ascii_digit() {
+        format!("number{}.png", character)
+    } else {
+        format!("letter{}.png", character)
+    }
+}
+
+/// Terrain and rocks of one biome.
+#[derive(Debug, Clone)]
+pub struct BiomeSprites {
+    pub ground: SheetSprite,
+    pub ceiling: SheetSprite,
+    pub rock: SheetSprite,
+    pub rock_down: SheetSprite,
+}
+
+/// The spritesheet resolved once against the config, so drawing a frame never looks a
+/// sprite up by name. Skins and biomes are found by their index in the config.
+#[derive(Debug, Clone)]
+pub struct Sprites {
+    fixed: Vec<SheetSprite>,
+    /// The propeller animation of every skin.
+    planes: Vec<Clip<SheetSprite>>,
+    biomes: Vec<BiomeSprites>,
+}
+
+impl Sprites {
+    /// Fails listing every sprite and animation the game or the config asks for that the
+    /// sheet lacks.
+    pub fn resolve(sheet: &Spritesheet, config: &GameConfig) -> Result<Self> {
+        let mut missing = Vec::new();
+        let mut missing_clips = Vec::new();
+        let mut find = |name: &str| match sheet.tileset.get(name) {
+            Some(sprite) => sprite.clone(),
+            None => {
+                missing.push(name.to_string());
+                SheetSprite::default()
+            }
+        };
+
+        // The font keeps its own glyphs, they are only checked here
+        for character in font_characters() {
+            find(&glyph_name(character));
+        }
+
+        let sprites = Sprites {
+            fixed: Sprite::ALL.iter().map(|sprite| find(sprite.file_name())).collect(),
+            planes: config.skins
+                .iter()
+                .map(|skin| match skin.animation.as_ref() {
+                    Some(name) => match sheet.clips.get(name) {
+                        Some(clip) => clip.map(|sprite| find(sprite)),
+                        None => {
+                            missing_clips.push(format!("{} (animation)", name));
+                            Clip { frames: Vec::new() }
+                        }
+                    },
+                    None => Clip {
+                        frames: skin.frame_names()
+                            .into_iter()
+                            .map(|name| ClipFrame { sprite: find(&name), duration: PROPELLER_FRAME_DURATION })
+                            .collect(),
+                    },
+                })
+                .collect(),
+            biomes: config.biomes.biomes
+                .iter()
+                .map(|biome| BiomeSprites {
+                    ground: find(&biome.ground),
+                    ceiling: find(&biome.ceiling),
+                    rock: find(&biome.rock),
+                    rock_down: find(&biome.rock_down),
+                })
+                .collect(),
+        };
+
+        missing.extend(missing_clips);
+        if missing.is_empty() {
+            Ok(sprites)
+        } else {
+            Err(anyhow!("The spritesheet has no sprite or animation named:\n - {}", missing.join("\n - ")))
+        }
+    }
+
+    /// Whether these were resolved for as many skins and biomes as `config` has.
+    pub fn fits(&self, config: &GameConfig) -> bool {
+        self.planes.len() == config.skins.len() && self.biomes.len() == config.biomes.biomes.len()
+    }
+
+    /// Propeller animation of the skin at `skin` in `GameConfig::skins`.
+    pub fn plane(&self, skin: usize) -> &Clip<SheetSprite> {
+        &self.planes[skin]
+    }
+
+    /// Sprites of the biome at `index` in `BiomeCycle::biomes`.
+    pub fn biome(&self, index: usize) -> &BiomeSprites {
+        &self.biomes[index]
+    }
+}
+
+impl Index<Sprite> for Sprites {
+    type Output = SheetSprite;
+
+    fn index(&self, sprite: Sprite) -> &SheetSprite {
+        &self.fixed[sprite as usize]
+    }
+}
+
+/// Vertices in pixels from the sprite's top-left corner.
+type Outline = Vec<[f32; 2]>;
+
+/// Rock outlines of one biome.
+#[derive(Debug, Clone)]
+pub struct BiomeOutlines {
+    pub rock: Option<Outline>,
+    pub rock_down: Option<Outline>,
+}
+
+/// Collider outlines resolved once against the config like `Sprites`, so spawning a body
+/// never looks one up by name. Kept apart from the sprites because runs fly without
+/// anything loaded to draw. Sprites without an outline collide as boxes.
+#[derive(Debug, Clone)]
+pub struct Outlines {
+    planes: Vec<Option<Outline>>,
+    biomes: Vec<BiomeOutlines>,
+}
+
+impl Outlines {
+    pub fn resolve(colliders: &ColliderOutlines, config: &GameConfig) -> Self {
+        Outlines {
+            planes: config.skins
+                .iter()
+                .map(|skin| colliders.get(&skin.frame_names()[0]).cloned())
+                .collect(),
+            biomes: config.biomes.biomes
+                .iter()
+                .map(|biome| BiomeOutlines {
+                    rock: colliders.get(&biome.rock).cloned(),
+                    rock_down: colliders.get(&biome.rock_down).cloned(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether these were resolved for as many skins and biomes as `config` has.
+    pub fn fits(&self, config: &GameConfig) -> bool {
+        self.planes.len() == config.skins.len() && self.biomes.len() == config.biomes.biomes.len()
+    }
+
+    /// Outline of the plane of the skin at `skin` in `GameConfig::skins`.
+    pub fn plane(&self, skin: usize) -> Option<&[[f32; 2]]> {
+        self.planes[skin].as_deref()
+    }
+
+    /// Rock outlines of the biome at `index` in `BiomeCycle::biomes`.
+    pub fn biome(&self, index: usize) -> &BiomeOutlines {
+        &self.biomes[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Rect;
+
+    fn sheet(names: &[String]) -> Spritesheet {
+        let tileset = names
+            .iter()
+            .map(|name| (name.clone(), SheetSprite { rect: Rect { x: 0, y: 0, width: 8, height: 8 }, ..SheetSprite::default() }))
+            .collect();
+        Spritesheet { tileset, ..Spritesheet::default() }
+    }
+
+    #[test]
+    fn missing_font_glyphs_are_listed() {
+        let config = GameConfig::default();
+        let mut names: Vec<String> = Sprite::ALL.iter().map(|sprite| sprite.file_name().to_string()).collect();
+        names.extend(config.skins.iter().flat_map(|skin| skin.frame_names()));
+        names.extend(config.biomes.biomes.iter().flat_map(|biome| {
+            [&biome.ground, &biome.ceiling, &biome.rock, &biome.rock_down].map(String::clone)
+        }));
+        names.extend(font_characters().filter(|character| *character != 'Q').map(glyph_name));
+
+        let err = Sprites::resolve(&sheet(&names), &config).unwrap_err().to_string();
+
+        assert!(err.ends_with("letterQ.png"), "{}", err);
+        names.push(glyph_name('Q'));
+        assert!(Sprites::resolve(&sheet(&names), &config).is_ok());
+    }
+
+    #[test]
+    fn outlines_follow_the_config_order() {
+        let config = GameConfig::default();
+        let second_skin = config.skins[1].frame_names()[0].clone();
+        let rock_down = config.biomes.biomes[0].rock_down.clone();
+        let colliders: ColliderOutlines = [
+            (second_skin, vec![[0.0, 0.0], [4.0, 0.0], [4.0, 4.0]]),
+            (rock_down, vec![[1.0, 1.0], [2.0, 2.0], [1.0, 2.0]]),
+        ]
+        .into_iter()
+        .collect();
+
+        let outlines = Outlines::resolve(&colliders, &config);
+
+        assert_eq!(outlines.plane(0), None);
+        assert_eq!(outlines.plane(1), Some(&[[0.0, 0.0], [4.0, 0.0], [4.0, 4.0]][..]));
+        assert!(outlines.biome(0).rock.is_none());
+        assert!(outlines.biome(0).rock_down.is_some());
+    }
+}
@@ -0,0 +1,104 @@
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf, rc::Rc};
+
+use game::{
+    atlas,
+    config::GameConfig,
+    engine::{Game, Image, Position, Renderer, Spritesheet},
+    game::{label_font, sprite_font, Fonts, Session, TappyPlane},
+    input::{Action, ActionMap, Binding, InputDevices, InputState},
+    replay::Frame,
+    outline::ColliderOutlines,
+    scores::{HighScores, MemoryStore},
+    software::{Bitmap, SoftwareBackend},
+    sprites::Sprites,
+};
+
+pub const DELTA: f64 = 1.0 / 60.0;
+
+pub fn asset(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../static").join(path.trim_start_matches('/'))
+}
+
+pub fn shipped_config() -> GameConfig {
+    GameConfig::from_json(&fs::read_to_string(asset("/assets/config.json")).unwrap()).unwrap()
+}
+
+/// The game as `TappyPlane::init` sets it up in the browser, with scores kept in memory.
+pub fn load_game(seed: u64) -> TappyPlane {
+    let mut sheet = Spritesheet::default();
+    for source in ["/assets/sheet.json", "/assets/Spritesheet/planes.json"] {
+        sheet = sheet.merge(atlas::read(&fs::read_to_string(asset(source)).unwrap(), source).unwrap()).unwrap();
+    }
+
+    let mut game = TappyPlane::new(seed);
+    game.images = sheet.images
+        .iter()
+        .map(|source| Image::Bitmap(Bitmap::from_png(&fs::read(asset(source)).unwrap()).unwrap()))
+        .collect();
+    game.fonts = Some(Fonts {
+        sprite: sprite_font(&sheet).unwrap(),
+        label: label_font(&fs::read(asset("/assets/Font/kenvector_future_thin.ttf")).unwrap()).unwrap(),
+    });
+    let colliders: ColliderOutlines = serde_json::from_str(&fs::read_to_string(asset("/assets/colliders.json")).unwrap()).unwrap();
+    let scores = HighScores::load(Box::<MemoryStore>::default(), 10).unwrap();
+    game.session = Session::new(seed, shipped_config(), scores, colliders);
+    game.sprites = Some(Sprites::resolve(&sheet, &game.session.config).unwrap());
+    game
+}
+
+/// Taps the pointer in the middle of the canvas and steps the game one tick at a time,
+/// keeping every tick as a replay frame.
+pub struct Player {
+    devices: InputDevices,
+    pub frames: Vec<Frame>,
+}
+
+impl Player {
+    pub fn new() -> Self {
+        let mut devices = InputDevices::new(ActionMap::default());
+        devices.move_pointer(Position { x: 400.0, y: 240.0 });
+        Player { devices, frames: Vec::new() }
+    }
+
+    fn step(&mut self, game: &mut impl Game) {
+        let input: InputState = self.devices.poll();
+        self.frames.push(Frame { delta: DELTA, input });
+        game.update(&DELTA, &input);
+    }
+
+    /// One tick with the pointer down, then one with it up.
+    pub fn tap(&mut self, game: &mut impl Game) {
+        self.devices.press(Binding::Pointer);
+        self.step(game);
+        self.devices.release(&Binding::Pointer);
+        self.step(game);
+    }
+
+    /// One tick with the key at `code` down, then one with it up.
+    pub fn press_key(&mut self, game: &mut impl Game, code: &str) {
+        let key = Binding::Key(code.to_string());
+        self.devices.press(key.clone());
+        self.step(game);
+        self.devices.release(&key);
+        self.step(game);
+    }
+
+    pub fn request(&mut self, game: &mut impl Game, action: Action) {
+        self.devices.request(action);
+        self.step(game);
+    }
+
+    pub fn wait(&mut self, game: &mut impl Game, ticks: usize) {
+        for _ in 0..ticks {
+            self.step(game);
+        }
+    }
+}
+
+pub fn render(game: &impl Game) -> Bitmap {
+    let backend = Rc::new(SoftwareBackend::new(800, 480));
+    game.draw(&Renderer::new(backend.clone()), &1.0);
+    backend.frame()
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use common::{load_game, render, Player};
+use game::{engine::Game, software::Bitmap};
+
+const SKY: [u8; 4] = [213, 237, 247, 255];
+const TAP_SIGN: [u8; 4] = [200, 62, 62, 255];
+const RED_PLANE: [u8; 4] = [194, 64, 64, 255];
+const COUNTDOWN_DIGIT: [u8; 4] = [255, 229, 127, 255];
+const STAR: [u8; 4] = [255, 204, 0, 255];
+const PANEL: [u8; 4] = [224, 209, 175, 255];
+const GAME_OVER_TITLE: [u8; 4] = [206, 77, 77, 255];
+
+fn assert_pixels(frame: &Bitmap, expected: &[((u32, u32), [u8; 4])]) {
+    for &((x, y), color) in expected {
+        assert_eq!(frame.pixel(x, y), color, "pixel at {}, {}", x, y);
+    }
+}
+
+#[test]
+fn draws_every_state_without_a_canvas() {
+    let mut game = load_game(7);
+    let mut player = Player::new();
+
+    assert_pixels(&render(&game), &[((600, 120), SKY), ((300, 250), TAP_SIGN), ((520, 238), TAP_SIGN), ((395, 205), RED_PLANE)]);
+
+    // Through the hangar, picking the plane under the pointer
+    player.tap(&mut game);
+    player.tap(&mut game);
+    player.wait(&mut game, 60);
+    assert_pixels(&render(&game), &[((600, 120), SKY), ((395, 205), COUNTDOWN_DIGIT), ((300, 250), [234, 245, 250, 255])]);
+
+    player.wait(&mut game, 200);
+    assert_eq!(game.score(), Some(0));
+    assert_pixels(&render(&game), &[((600, 120), SKY), ((35, 100), STAR), ((381, 118), [255, 255, 255, 255])]);
+
+    // Never flapping, the plane falls onto the ground
+    player.wait(&mut game, 300);
+    assert_pixels(&render(&game), &[((600, 120), SKY), ((300, 250), PANEL), ((381, 118), GAME_OVER_TITLE)]);
+}
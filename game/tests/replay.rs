@@ -0,0 +1,105 @@
+mod common;
+
+use common::{load_game, render, Player};
+use game::{
+    config::GameConfig,
+    engine::Game,
+    game::{Session, TappyPlane},
+    outline::ColliderOutlines,
+    replay::Replay,
+    scores::{HighScores, MemoryStore, ScoreEntry},
+};
+
+const SEED: u64 = 42;
+const TAPS: usize = 12;
+const CADENCE: usize = 40;
+
+fn past_run(score: u32) -> ScoreEntry {
+    ScoreEntry { date: 0.0, score, plane_color: String::from("Red"), seed: 1, stars: 0 }
+}
+
+/// Picks the second skin, which only the recorded score table unlocks, and flies it
+/// with a steady tap.
+fn record() -> (TappyPlane, Replay) {
+    let mut game = load_game(SEED);
+    game.session.scores = HighScores::load(Box::new(MemoryStore::new(vec![past_run(30)])), 10).unwrap();
+    let mut replay = Replay::new(SEED);
+    replay.begin(game.session_start());
+
+    let mut player = Player::new();
+    player.tap(&mut game);
+    player.press_key(&mut game, "ArrowRight");
+    player.press_key(&mut game, "Enter");
+    player.wait(&mut game, 200);
+    for _ in 0..TAPS {
+        player.tap(&mut game);
+        player.wait(&mut game, CADENCE);
+    }
+
+    replay.frames = player.frames;
+    (game, replay)
+}
+
+/// Entries without their date, which is whenever the run was flown.
+fn runs(game: &TappyPlane) -> Vec<(u32, String, u64)> {
+    game.session.scores.entries().iter().map(|entry| (entry.score, entry.plane_color.clone(), entry.seed)).collect()
+}
+
+#[test]
+fn replays_rebuild_the_recorded_session() {
+    let (recorded, replay) = record();
+    assert_eq!(recorded.session.skin, 1);
+    assert!(recorded.session.scores.entries().iter().any(|entry| entry.plane_color == "Blue"));
+
+    // Whoever watches has none of the player's files or scores
+    let mut watched = load_game(SEED);
+    let scores = HighScores::load(Box::<MemoryStore>::default(), 10).unwrap();
+    watched.session = Session::new(SEED, GameConfig::default(), scores, ColliderOutlines::new());
+    Replay::from_json(&replay.to_json().unwrap()).unwrap().play(&mut watched).unwrap();
+
+    assert_eq!(watched.session.skin, recorded.session.skin);
+    assert_eq!(runs(&watched), runs(&recorded));
+    assert_eq!(watched.score(), recorded.score());
+    assert_eq!(render(&watched), render(&recorded));
+}
+
+#[test]
+fn replays_recorded_before_loading_are_refused() {
+    let mut game = load_game(SEED);
+
+    assert!(Replay::new(SEED).play(&mut game).is_err());
+}
+
+/// A replay of a game started from `config`, with no input.
+fn replay_of(config: GameConfig) -> Replay {
+    let game = load_game(SEED);
+    let mut start = game.session_start();
+    start.config = config;
+    let mut replay = Replay::new(SEED);
+    replay.begin(start);
+    replay
+}
+
+#[test]
+fn replays_of_other_skins_or_biomes_are_refused() {
+    let mut fewer_skins = load_game(SEED).session.config.clone();
+    fewer_skins.skins.pop();
+    let mut fewer_biomes = load_game(SEED).session.config.clone();
+    fewer_biomes.biomes.biomes.pop();
+
+    for config in [fewer_skins, fewer_biomes] {
+        let mut game = load_game(SEED);
+        let err = replay_of(config).play(&mut game).unwrap_err();
+        assert!(err.to_string().contains("other skins or biomes"), "{}", err);
+        assert_eq!(game.session.config.skins.len(), 4);
+    }
+}
+
+#[test]
+fn replays_of_invalid_configs_are_refused() {
+    let mut no_skins = load_game(SEED).session.config.clone();
+    no_skins.skins.clear();
+    let mut game = load_game(SEED);
+
+    assert!(replay_of(no_skins).play(&mut game).is_err());
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::{load_game, Player};
+use game::engine::Game;
+
+/// A course and a steady tap that clear the first rock and pick up stars worth 3 before
+/// hitting the second.
+const SEED: u64 = 13;
+const CADENCE: usize = 70;
+
+#[test]
+fn passing_rocks_scores_and_stars_are_kept_apart() {
+    let mut game = load_game(SEED);
+    let mut player = Player::new();
+    player.tap(&mut game);
+    player.tap(&mut game);
+    player.wait(&mut game, 260);
+    assert_eq!(game.score(), Some(0));
+
+    let mut scores = vec![0];
+    let mut ticks = 0;
+    while game.session.scores.entries().is_empty() {
+        if ticks % CADENCE == 0 {
+            player.tap(&mut game);
+        } else {
+            player.wait(&mut game, 1);
+        }
+        ticks += 1;
+        assert!(ticks < 3000, "The plane never crashed");
+
+        let score = game.score().unwrap();
+        if score != *scores.last().unwrap() {
+            scores.push(score);
+        }
+    }
+
+    // Only the rock counts, not the stars on the way
+    assert_eq!(scores, [0, 1]);
+    let run = &game.session.scores.entries()[0];
+    assert_eq!((run.score, run.stars), (1, 3));
+}